@@ -0,0 +1,102 @@
+use crate::config::inputs::{optional_input, required_input};
+use crate::error::{Error, Result};
+use crate::google_sheets::auth::{ServiceAccountKey, ServiceAccountTokenSource};
+use crate::google_sheets::oauth::{OAuthClientConfig, OAuthTokenSource};
+use crate::google_sheets::token_provider::{
+    CachedTokenProvider, ShardedTokenProvider, ShardingStrategy, TokenSource,
+};
+use crate::google_sheets::workload_identity::{WorkloadIdentityConfig, WorkloadIdentityTokenSource};
+use crate::http::client::ApiClient;
+use std::path::PathBuf;
+
+/// Builds the token provider from the inputs: workload identity federation, a signed-in user
+/// or one or more service account keys. Several keys share the requests among them.
+pub async fn token_provider_from_inputs(
+    client: &ApiClient,
+    scope: &str,
+    delegated_user: Option<&str>,
+) -> Result<ShardedTokenProvider> {
+    let strategy = match optional_input("INPUT_SHARDING").as_deref().map(str::trim) {
+        None | Some("round-robin") => ShardingStrategy::RoundRobin,
+        Some("on-quota") => ShardingStrategy::OnQuota,
+        Some(other) => {
+            return Err(Error::config(format!(
+                "INPUT_SHARDING must be 'round-robin' or 'on-quota', got '{}'",
+                other
+            )))
+        }
+    };
+    let mut provider = ShardedTokenProvider::new(strategy);
+    if let Some(limit) = optional_input("INPUT_SHARD_REQUESTS_PER_MINUTE") {
+        let limit = limit.trim().parse().map_err(|_| {
+            Error::config(format!("INPUT_SHARD_REQUESTS_PER_MINUTE must be a number, got '{}'", limit))
+        })?;
+        provider = provider.with_requests_per_minute(limit);
+    }
+
+    for (account, source) in token_sources(client, scope, delegated_user).await? {
+        // Access tokens are cached and refreshed before they expire
+        let mut cached = CachedTokenProvider::new(source);
+        if let Some(cache_dir) = optional_input("INPUT_TOKEN_CACHE_DIR") {
+            cached = cached.with_cache_dir(PathBuf::from(cache_dir));
+        }
+        provider = provider.with_account(&account, cached);
+    }
+    Ok(provider)
+}
+
+async fn token_sources(
+    client: &ApiClient,
+    scope: &str,
+    delegated_user: Option<&str>,
+) -> Result<Vec<(String, Box<dyn TokenSource>)>> {
+    if let Some(client_id) = optional_input("INPUT_OAUTH_CLIENT_ID") {
+        if delegated_user.is_some() {
+            return Err(Error::config(
+                "domain-wide delegation needs a service account key, it can't be used with a signed-in user",
+            ));
+        }
+        let client_secret = optional_input("INPUT_OAUTH_CLIENT_SECRET");
+        let mut config = OAuthClientConfig::new(&client_id, client_secret.as_deref())?;
+        if let Some(credentials_dir) = optional_input("INPUT_OAUTH_CREDENTIALS_DIR") {
+            config.credentials_dir = PathBuf::from(credentials_dir);
+        }
+        let source = OAuthTokenSource::load_or_authorize(client.clone(), config, scope).await?;
+        return Ok(vec![(client_id, Box::new(source))]);
+    }
+
+    if let Some(provider) = optional_input("INPUT_WORKLOAD_IDENTITY_PROVIDER") {
+        if delegated_user.is_some() {
+            return Err(Error::config(
+                "domain-wide delegation needs a service account key, it can't be used with workload identity",
+            ));
+        }
+        let service_account = required_input("INPUT_SERVICE_ACCOUNT")?;
+        let config = WorkloadIdentityConfig::from_env(&provider, &service_account)?;
+        let source = WorkloadIdentityTokenSource::new(client.clone(), config, scope);
+        return Ok(vec![(service_account, Box::new(source))]);
+    }
+
+    // Service account keys, either whole JSON keys or the email and the private key
+    let keys = match optional_input("INPUT_CREDENTIALS") {
+        Some(credentials) => ServiceAccountKey::load_all(&credentials)?,
+        None => vec![ServiceAccountKey::from_parts(
+            &required_input("INPUT_ROBOT_EMAIL")?,
+            &required_input("INPUT_PRIVATE_API_KEY")?,
+        )?],
+    };
+    if keys.is_empty() {
+        return Err(Error::config("INPUT_CREDENTIALS contains no service account keys"));
+    }
+
+    let mut sources: Vec<(String, Box<dyn TokenSource>)> = Vec::new();
+    for key in keys {
+        let account = key.client_email.clone();
+        let mut source = ServiceAccountTokenSource::new(client.clone(), key, scope);
+        if let Some(delegated_user) = delegated_user {
+            source = source.with_subject(delegated_user);
+        }
+        sources.push((account, Box::new(source)));
+    }
+    Ok(sources)
+}
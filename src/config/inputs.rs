@@ -0,0 +1,40 @@
+use crate::error::{Error, Result};
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+// Inputs of the config profile of the course, see `set_profile`
+static PROFILE: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// Reads an input of the action, treating a blank value as missing.
+///
+/// An input of the config profile, if one is set, overrides the input of the action: the profile
+/// is specific to the graded repository, the workflow may be shared by several courses.
+pub fn optional_input(name: &str) -> Option<String> {
+    let is_set = |value: &String| !value.trim().is_empty();
+    PROFILE
+        .get()
+        .and_then(|profile| profile.get(name).cloned())
+        .filter(is_set)
+        .or_else(|| env::var(name).ok().filter(is_set))
+}
+
+pub fn required_input(name: &str) -> Result<String> {
+    optional_input(name).ok_or_else(|| Error::config(format!("input {} is not set", name)))
+}
+
+/// Sets the config profile: inputs (e.g. `INPUT_STATUS_VALUES`) used instead of the inputs of the action.
+///
+/// The profile can be set once, before the inputs are read.
+pub fn set_profile(inputs: HashMap<String, String>) -> Result<()> {
+    PROFILE
+        .set(inputs)
+        .map_err(|_| Error::config("The config profile is already set"))
+}
+
+/// Resolves a path given in the inputs, relative paths are relative to the checkout.
+pub fn workspace_path(path: &str) -> PathBuf {
+    let workspace = env::var("GITHUB_WORKSPACE").unwrap_or_else(|_| ".".to_string());
+    PathBuf::from(workspace).join(path.trim())
+}
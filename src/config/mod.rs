@@ -0,0 +1,4 @@
+pub mod auth;
+pub mod inputs;
+pub mod routing;
+pub mod targets;
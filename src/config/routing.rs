@@ -0,0 +1,202 @@
+use crate::config::inputs::{optional_input, workspace_path};
+use crate::error::{Error, Result};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+
+/// Routing of the repositories of several courses, read from `INPUT_ROUTES`.
+///
+/// ```json
+/// {
+///   "profiles": {"algorithms": {"status-values": {"timeout": "TL"}, "autograding-config": ".github/classroom/autograding.json"}},
+///   "routes": [
+///     {"org": "algo-2026", "repo": "hw\\d+-.*", "spreadsheet_id": "1abc", "sheet": "Homework", "profile": "algorithms"},
+///     {"org": "web-2026", "spreadsheet_id": "1def"}
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct RoutingConfig {
+    /// Profile name -> inputs of the action, by input name (`status-values`) or variable (`INPUT_STATUS_VALUES`).
+    #[serde(default)]
+    pub profiles: HashMap<String, HashMap<String, Value>>,
+    /// Checked in order, the first matching route is taken.
+    pub routes: Vec<Route>,
+}
+
+/// Gradebook and profile of the repositories of an organization.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Route {
+    /// Organization (or user) owning the repository, any when missing. Case-insensitive.
+    pub org: Option<String>,
+    /// Regular expression matching the whole repository name, any when missing.
+    pub repo: Option<String>,
+    pub spreadsheet_id: Option<String>,
+    pub sheet: Option<String>,
+    pub profile: Option<String>,
+    // `repo` anchored to the whole name, compiled when the routes are parsed
+    #[serde(skip)]
+    repo_regex: Option<Regex>,
+}
+
+impl Route {
+    fn matches(&self, org: &str, repo: &str) -> bool {
+        if let Some(route_org) = &self.org {
+            if !route_org.trim().eq_ignore_ascii_case(org) {
+                return false;
+            }
+        }
+        self.repo_regex.as_ref().is_none_or(|regex| regex.is_match(repo))
+    }
+
+    /// Label of the route in messages, e.g. `algo-2026/hw\d+-.*`.
+    pub fn describe(&self) -> String {
+        format!(
+            "{}/{}",
+            self.org.as_deref().unwrap_or("*"),
+            self.repo.as_deref().unwrap_or(".*")
+        )
+    }
+}
+
+impl RoutingConfig {
+    /// Parses the routes, given as JSON or as the path of a JSON file in the checkout.
+    pub fn parse(routes: &str) -> Result<Self> {
+        let routes = routes.trim();
+        let (json, source) = if routes.starts_with('{') {
+            (routes.to_string(), "INPUT_ROUTES".to_string())
+        } else {
+            let path = workspace_path(routes);
+            let json = fs::read_to_string(&path)
+                .map_err(|e| Error::config(format!("Can't read the routes {}: {}", path.display(), e)))?;
+            (json, path.display().to_string())
+        };
+        let mut config: RoutingConfig = serde_json::from_str(&json)
+            .map_err(|e| Error::config(format!("Invalid routes in {}: {}", source, e)))?;
+
+        // A typo in any pattern fails every run, not only the runs that reach its route
+        for route in &mut config.routes {
+            if let Some(pattern) = &route.repo {
+                let regex = Regex::new(&format!("^(?:{})$", pattern))
+                    .map_err(|e| Error::config(format!("Invalid repo pattern '{}' in the routes: {}", pattern, e)))?;
+                route.repo_regex = Some(regex);
+            }
+        }
+
+        // Catch a misspelled profile before it silently routes to the default inputs
+        for route in &config.routes {
+            if let Some(profile) = &route.profile {
+                if !config.profiles.contains_key(profile) {
+                    return Err(Error::config(format!(
+                        "The route {} uses the unknown profile '{}'",
+                        route.describe(),
+                        profile
+                    )));
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// The first route matching `repository` (`org/repo`).
+    pub fn route(&self, repository: &str) -> Result<&Route> {
+        let (org, repo) = repository.split_once('/').ok_or_else(|| {
+            Error::config(format!("Repository '{}' is not of the form org/repo", repository))
+        })?;
+        self.routes
+            .iter()
+            .find(|route| route.matches(org, repo))
+            .ok_or_else(|| Error::config(format!("No route matches the repository {}", repository)))
+    }
+
+    /// Inputs set by the route: the inputs of its profile, its spreadsheet (`INPUT_TABLE_ID`)
+    /// and its sheet (`INPUT_SHEET_NAME`).
+    pub fn inputs(&self, route: &Route) -> HashMap<String, String> {
+        let mut inputs = HashMap::new();
+        if let Some(profile) = route.profile.as_ref().and_then(|name| self.profiles.get(name)) {
+            for (name, value) in profile {
+                // Structured inputs (status values, targets) can be written as JSON
+                let value = match value {
+                    Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                };
+                inputs.insert(input_variable(name), value);
+            }
+        }
+        if let Some(spreadsheet_id) = &route.spreadsheet_id {
+            inputs.insert("INPUT_TABLE_ID".to_string(), spreadsheet_id.clone());
+        }
+        if let Some(sheet) = &route.sheet {
+            inputs.insert("INPUT_SHEET_NAME".to_string(), sheet.clone());
+        }
+        inputs
+    }
+}
+
+// `status-values` -> `INPUT_STATUS_VALUES`, the variable the action sets for the input
+fn input_variable(name: &str) -> String {
+    let name = name.trim().to_uppercase().replace('-', "_");
+    if name.starts_with("INPUT_") {
+        name
+    } else {
+        format!("INPUT_{}", name)
+    }
+}
+
+/// Route of the graded repository (`GITHUB_REPOSITORY`), when `INPUT_ROUTES` is set.
+pub fn route_from_inputs() -> Result<Option<(RoutingConfig, Route)>> {
+    let routes = match optional_input("INPUT_ROUTES") {
+        Some(routes) => routes,
+        None => return Ok(None),
+    };
+    let config = RoutingConfig::parse(&routes)?;
+    let repository = std::env::var("GITHUB_REPOSITORY")
+        .ok()
+        .filter(|repository| !repository.is_empty())
+        .ok_or_else(|| Error::config("INPUT_ROUTES is set but GITHUB_REPOSITORY is not"))?;
+    let route = config.route(&repository)?.clone();
+    Ok(Some((config, route)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROUTES: &str = r#"{
+        "profiles": {"algo": {"status-values": {"error": "ERR"}}},
+        "routes": [
+            {"org": "Algo-2026", "repo": "hw\\d+-.*", "spreadsheet_id": "algo", "sheet": "Homework", "profile": "algo"},
+            {"org": "web-2026", "spreadsheet_id": "web"}
+        ]
+    }"#;
+
+    #[test]
+    fn takes_the_first_matching_route() {
+        let config = RoutingConfig::parse(ROUTES).unwrap();
+        let route = config.route("algo-2026/hw3-octocat").unwrap();
+        assert_eq!(route.spreadsheet_id.as_deref(), Some("algo"));
+        let inputs = config.inputs(route);
+        assert_eq!(inputs["INPUT_TABLE_ID"], "algo");
+        assert_eq!(inputs["INPUT_SHEET_NAME"], "Homework");
+        assert_eq!(inputs["INPUT_STATUS_VALUES"], r#"{"error":"ERR"}"#);
+
+        assert_eq!(config.route("web-2026/site").unwrap().spreadsheet_id.as_deref(), Some("web"));
+        // The pattern matches the whole name
+        assert!(config.route("algo-2026/lab-hw3-octocat").is_err());
+    }
+
+    #[test]
+    fn rejects_an_invalid_pattern_of_any_route() {
+        let routes = r#"{"routes": [{"org": "a", "spreadsheet_id": "x"}, {"org": "b", "repo": "hw(", "spreadsheet_id": "y"}]}"#;
+        let err = RoutingConfig::parse(routes).unwrap_err();
+        assert_eq!(err.kind(), crate::error::ErrorKind::Config);
+    }
+
+    #[test]
+    fn rejects_an_unknown_profile() {
+        let err = RoutingConfig::parse(r#"{"routes": [{"profile": "nope"}]}"#).unwrap_err();
+        assert_eq!(err.kind(), crate::error::ErrorKind::Config);
+    }
+}
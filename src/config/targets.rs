@@ -0,0 +1,56 @@
+use crate::config::inputs::{optional_input, required_input};
+use crate::error::{Error, Result};
+use serde::Deserialize;
+
+/// Sheet of a Google spreadsheet the grades are written to.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SheetTarget {
+    pub spreadsheet_id: String,
+    /// Name of the tab, `sheet-name` (or `Sheet1`) by default.
+    #[serde(default = "sheet_name")]
+    pub sheet: String,
+    /// Workspace user the service account acts as for this spreadsheet, `delegated-user` by default.
+    #[serde(default = "delegated_user")]
+    pub delegated_user: Option<String>,
+}
+
+/// Name of the gradebook sheet, `INPUT_SHEET_NAME` or `Sheet1`.
+pub fn sheet_name() -> String {
+    optional_input("INPUT_SHEET_NAME")
+        .map(|name| name.trim().to_string())
+        .unwrap_or_else(|| "Sheet1".to_string())
+}
+
+fn delegated_user() -> Option<String> {
+    optional_input("INPUT_DELEGATED_USER")
+}
+
+/// Parses a JSON list of targets, e.g.
+/// `[{"spreadsheet_id": "1abc", "sheet": "Section A"}, {"spreadsheet_id": "1def"}]`.
+pub fn parse_targets(json: &str, what: &str) -> Result<Vec<SheetTarget>> {
+    let targets: Vec<SheetTarget> = serde_json::from_str(json).map_err(|e| {
+        Error::config(format!(
+            "{} must be a JSON list of {{\"spreadsheet_id\", \"sheet\", \"delegated_user\"}} objects: {}",
+            what, e
+        ))
+    })?;
+    if targets.is_empty() {
+        return Err(Error::config(format!("{} has no targets", what)));
+    }
+    if let Some(target) = targets.iter().find(|target| target.spreadsheet_id.trim().is_empty()) {
+        return Err(Error::config(format!("{} has a target without a spreadsheet_id ({:?})", what, target)));
+    }
+    Ok(targets)
+}
+
+/// The sheets of `INPUT_TARGETS`, or the single sheet of `INPUT_TABLE_ID`.
+pub fn targets_from_inputs() -> Result<Vec<SheetTarget>> {
+    match optional_input("INPUT_TARGETS") {
+        Some(json) => parse_targets(&json, "INPUT_TARGETS"),
+        None => Ok(vec![SheetTarget {
+            spreadsheet_id: required_input("INPUT_TABLE_ID")?.trim().to_string(),
+            sheet: sheet_name(),
+            delegated_user: delegated_user(),
+        }]),
+    }
+}
@@ -1,2 +1,4 @@
 pub mod parser;
+pub mod roster;
+pub mod table;
 pub mod utils;
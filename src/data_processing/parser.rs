@@ -1,6 +1,7 @@
+use crate::error::Result;
 use serde_json::Value;
 
-pub fn parse_sheet_data(data: &Value) -> Result<Vec<Vec<String>>, Box<dyn std::error::Error>> {
+pub fn parse_sheet_data(data: &Value) -> Result<Vec<Vec<String>>> {
     let mut table = Vec::new();
     if let Some(values) = data["values"].as_array() {
         for row in values {
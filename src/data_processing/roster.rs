@@ -0,0 +1,70 @@
+use crate::error::{Error, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Reads a mapping (e.g. github_id -> LMS user id) from a CSV file with a header row.
+///
+/// The key of a row is its first non-empty cell of `key_columns`, so the roster exported by
+/// GitHub Classroom (`github_username`) works as well as a `github_id` column.
+/// Rows with an empty key or value are skipped.
+pub fn load_mapping(path: &Path, key_columns: &[&str], value_column: &str) -> Result<HashMap<String, String>> {
+    let content = fs::read(path)
+        .map_err(|e| Error::config(format!("Can't read the roster {}: {}", path.display(), e)))?;
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(content.as_slice());
+    let invalid = |e: csv::Error| Error::parse(format!("Invalid CSV in {}: {}", path.display(), e));
+
+    let headers = reader.headers().map_err(invalid)?.clone();
+    let position = |name: &str| headers.iter().position(|header| header.trim() == name);
+    let key_cols: Vec<usize> = key_columns.iter().filter_map(|name| position(name)).collect();
+    if key_cols.is_empty() {
+        return Err(Error::not_found(format!(
+            "Column '{}' not found in {}",
+            key_columns.join("' or '"),
+            path.display()
+        )));
+    }
+    let value_col = position(value_column).ok_or_else(|| {
+        Error::not_found(format!("Column '{}' not found in {}", value_column, path.display()))
+    })?;
+
+    let mut mapping = HashMap::new();
+    for record in reader.records() {
+        let record = record.map_err(invalid)?;
+        let key = key_cols
+            .iter()
+            .map(|col| record.get(*col).unwrap_or("").trim())
+            .find(|key| !key.is_empty())
+            .unwrap_or("");
+        let value = record.get(value_col).unwrap_or("").trim();
+        if !key.is_empty() && !value.is_empty() {
+            mapping.insert(key.to_string(), value.to_string());
+        }
+    }
+    Ok(mapping)
+}
+
+/// Parses a JSON object mapping names to ids, e.g. `{"test-1": 1234, "test-2": "5678"}`.
+pub fn parse_mapping(json: &str, what: &str) -> Result<HashMap<String, String>> {
+    let data: HashMap<String, Value> = serde_json::from_str(json)
+        .map_err(|e| Error::config(format!("{} must be a JSON object of ids: {}", what, e)))?;
+
+    let mut mapping = HashMap::new();
+    for (name, id) in data {
+        let id = match id {
+            Value::String(id) => id,
+            Value::Number(id) => id.to_string(),
+            other => {
+                return Err(Error::config(format!(
+                    "{}: the id of '{}' must be a number or a string, got {}",
+                    what, name, other
+                )))
+            }
+        };
+        mapping.insert(name, id);
+    }
+    Ok(mapping)
+}
@@ -0,0 +1,138 @@
+use crate::data_processing::utils::{
+    find_column_by_header, find_first_empty_column, find_first_empty_row,
+};
+use crate::error::{Error, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+
+// The rules of StudentManager for gradebooks kept in memory (files, workbooks)
+
+/// Text written into the cell for the value, strings are written without quotes.
+pub fn cell_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Sets the cell, adding rows and columns if the table is too small.
+pub fn set_cell(table: &mut Vec<Vec<String>>, row: usize, col: usize, value: String) {
+    if table.len() <= row {
+        table.resize(row + 1, Vec::new());
+    }
+    if table[row].len() <= col {
+        table[row].resize(col + 1, String::new());
+    }
+    table[row][col] = value;
+}
+
+pub fn get_cell(table: &[Vec<String>], row: usize, col: usize) -> Option<String> {
+    table
+        .get(row)
+        .and_then(|cells| cells.get(col))
+        .filter(|cell| !cell.is_empty())
+        .cloned()
+}
+
+/// Returns the row of the student, appending a new row if the student is not in the table.
+/// An empty table gets the `github_id` header first.
+pub fn get_or_create_student_row(
+    table: &mut Vec<Vec<String>>,
+    github_id: &str,
+    table_name: &str,
+) -> Result<usize> {
+    if table.iter().all(|row| row.iter().all(|cell| cell.trim().is_empty())) {
+        table.clear();
+        set_cell(table, 0, 0, "github_id".to_string());
+    }
+
+    // Find the column index for 'github_id'
+    let github_id_col = find_column_by_header(table, "github_id")
+        .ok_or_else(|| Error::not_found(format!("Column 'github_id' not found in {}", table_name)))?;
+
+    // Search for the student by GitHub ID, skipping the header
+    let existing = table
+        .iter()
+        .skip(1)
+        .position(|row| row.get(github_id_col).is_some_and(|cell| cell == github_id));
+    if let Some(index) = existing {
+        return Ok(index + 1);
+    }
+
+    // Student not found, create a new record
+    let new_row_idx = find_first_empty_row(table);
+    set_cell(table, new_row_idx, github_id_col, github_id.to_string());
+    Ok(new_row_idx)
+}
+
+/// Returns the column of the assignment, adding it in the first empty column if it is not in the header.
+pub fn get_or_create_assignment_column(table: &mut Vec<Vec<String>>, assignment_name: &str) -> usize {
+    if table.is_empty() {
+        set_cell(table, 0, 0, "github_id".to_string());
+    }
+
+    if let Some(col_idx) = find_column_by_header(table, assignment_name) {
+        return col_idx;
+    }
+
+    let new_col_idx = find_first_empty_column(&table[..1]);
+    set_cell(table, 0, new_col_idx, assignment_name.to_string());
+    new_col_idx
+}
+
+/// Cells of `after` that differ from `before`, to write only the difference.
+/// Cells in `values` keep their JSON type (numbers stay numbers), the others are text.
+pub fn changed_cells(
+    before: &[Vec<String>],
+    after: &[Vec<String>],
+    mut values: HashMap<(usize, usize), Value>,
+) -> Vec<(usize, usize, Value)> {
+    let mut cells = Vec::new();
+    for (row_idx, row) in after.iter().enumerate() {
+        for (col_idx, text) in row.iter().enumerate() {
+            if get_cell(before, row_idx, col_idx).unwrap_or_default() != *text {
+                let value = values
+                    .remove(&(row_idx, col_idx))
+                    .unwrap_or_else(|| Value::String(text.clone()));
+                cells.push((row_idx, col_idx, value));
+            }
+        }
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn table(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter().map(|row| row.iter().map(|cell| cell.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn changed_cells_are_only_the_difference() {
+        let before = table(&[&["github_id", "task01"], &["alice", "1"]]);
+        let after = table(&[&["github_id", "task01", "task02"], &["alice", "1", ""], &["bob", "0", "1"]]);
+        let values = HashMap::from([((2, 1), json!(0)), ((2, 2), json!(1))]);
+
+        assert_eq!(
+            changed_cells(&before, &after, values),
+            vec![
+                (0, 2, json!("task02")),
+                (2, 0, json!("bob")),
+                (2, 1, json!(0)),
+                (2, 2, json!(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn unchanged_table_has_no_changed_cells() {
+        let before = table(&[&["github_id", "task01"], &["alice", "1"]]);
+        // A value written with the same text as before is not written again
+        let values = HashMap::from([((1, 1), json!(1))]);
+        assert!(changed_cells(&before, &before.clone(), values).is_empty());
+    }
+}
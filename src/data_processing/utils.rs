@@ -1,7 +1,7 @@
-pub fn find_row_by_word(table: &Vec<Vec<String>>, word: &str) -> Option<usize> {
+pub fn find_row_by_word(table: &[Vec<String>], word: &str) -> Option<usize> {
     for (i, row) in table.iter().enumerate() {
-        if row.get(0).map_or(false, |cell| cell == word)
-            || row.get(1).map_or(false, |cell| cell == word)
+        if row.first().is_some_and(|cell| cell == word)
+            || row.get(1).is_some_and(|cell| cell == word)
         {
             return Some(i);
         }
@@ -9,8 +9,8 @@ pub fn find_row_by_word(table: &Vec<Vec<String>>, word: &str) -> Option<usize> {
     None
 }
 
-pub fn find_column_by_header(table: &Vec<Vec<String>>, header: &str) -> Option<usize> {
-    if let Some(first_row) = table.get(0) {
+pub fn find_column_by_header(table: &[Vec<String>], header: &str) -> Option<usize> {
+    if let Some(first_row) = table.first() {
         for (i, cell) in first_row.iter().enumerate() {
             if cell == header {
                 return Some(i);
@@ -20,7 +20,7 @@ pub fn find_column_by_header(table: &Vec<Vec<String>>, header: &str) -> Option<u
     None
 }
 
-pub fn find_first_empty_row(table: &Vec<Vec<String>>) -> usize {
+pub fn find_first_empty_row(table: &[Vec<String>]) -> usize {
     for (i, row) in table.iter().enumerate() {
         if row.iter().all(|cell| cell.trim().is_empty()) {
             return i;
@@ -29,7 +29,7 @@ pub fn find_first_empty_row(table: &Vec<Vec<String>>) -> usize {
     table.len()
 }
 
-pub fn find_first_empty_column(table: &Vec<Vec<String>>) -> usize {
+pub fn find_first_empty_column(table: &[Vec<String>]) -> usize {
     if table.is_empty() {
         return 0;
     }
@@ -59,6 +59,31 @@ pub fn coords_to_cell_address(row: usize, col: usize) -> String {
     format!("{}{}", column_letters, row_number)
 }
 
+/// Range of a sheet in A1 notation, e.g. `'Section A'!B2:C3`. The name is quoted, so any sheet name works.
+pub fn sheet_range(sheet_name: &str, range: &str) -> String {
+    format!("'{}'!{}", sheet_name.replace('\'', "''"), range)
+}
+
+/// Parses an A1 address like `C7` (or `Sheet1!C7:F9`, the first cell is taken) into zero-based coordinates.
+pub fn cell_address_to_coords(address: &str) -> Option<(usize, usize)> {
+    let cell = address.rsplit('!').next()?.split(':').next()?.replace('$', "");
+    let split = cell.find(|c: char| c.is_ascii_digit())?;
+    let (letters, digits) = cell.split_at(split);
+    if letters.is_empty() {
+        return None;
+    }
+
+    let mut col = 0;
+    for letter in letters.chars() {
+        if !letter.is_ascii_alphabetic() {
+            return None;
+        }
+        col = col * 26 + (letter.to_ascii_uppercase() as usize - 'A' as usize + 1);
+    }
+    let row: usize = digits.parse().ok()?;
+    Some((row.checked_sub(1)?, col - 1))
+}
+
 fn number_to_column_letters(mut col_num: usize) -> String {
     let mut letters = String::new();
     col_num += 1; // Google sheets use 1-indexing for columns
@@ -71,3 +96,35 @@ fn number_to_column_letters(mut col_num: usize) -> String {
     letters
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cell_addresses() {
+        assert_eq!(cell_address_to_coords("A1"), Some((0, 0)));
+        assert_eq!(cell_address_to_coords("c7"), Some((6, 2)));
+        assert_eq!(cell_address_to_coords("AA10"), Some((9, 26)));
+        // Graph addresses of used ranges: the sheet and the end of the range are dropped
+        assert_eq!(cell_address_to_coords("Sheet1!B2:D9"), Some((1, 1)));
+        assert_eq!(cell_address_to_coords("'Section A'!$C$3"), Some((2, 2)));
+        assert_eq!(cell_address_to_coords(&coords_to_cell_address(41, 53)), Some((41, 53)));
+    }
+
+    #[test]
+    fn rejects_invalid_cell_addresses() {
+        assert_eq!(cell_address_to_coords(""), None);
+        assert_eq!(cell_address_to_coords("B"), None);
+        assert_eq!(cell_address_to_coords("12"), None);
+        assert_eq!(cell_address_to_coords("A0"), None);
+        assert_eq!(cell_address_to_coords("A-1"), None);
+    }
+
+    #[test]
+    fn quotes_the_sheet_name_of_ranges() {
+        assert_eq!(sheet_range("Sheet1", "A1:Z"), "'Sheet1'!A1:Z");
+        assert_eq!(sheet_range("Section A", "B2"), "'Section A'!B2");
+        assert_eq!(sheet_range("Bob's grades", "A:A"), "'Bob''s grades'!A:A");
+    }
+}
@@ -0,0 +1,344 @@
+use serde_json::Value;
+use std::fmt;
+
+/// Category of an [`Error`], each one has its own process exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Missing or invalid inputs and configuration files.
+    Config,
+    /// Credentials were rejected or an access token could not be obtained.
+    Auth,
+    /// The account has no access to the requested resource.
+    Permission,
+    /// Rate limit or quota exceeded.
+    Quota,
+    /// Spreadsheet, sheet, column or other resource does not exist.
+    NotFound,
+    /// Malformed test results or unexpected API responses.
+    Parse,
+    /// Connection problems, timeouts and other transport errors.
+    Network,
+    /// Any other unsuccessful API response.
+    Api,
+}
+
+impl ErrorKind {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ErrorKind::Config => 2,
+            ErrorKind::Auth => 3,
+            ErrorKind::Permission => 4,
+            ErrorKind::Quota => 5,
+            ErrorKind::NotFound => 6,
+            ErrorKind::Parse => 7,
+            ErrorKind::Network => 8,
+            ErrorKind::Api => 9,
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            ErrorKind::Config => "configuration error",
+            ErrorKind::Auth => "authentication failed",
+            ErrorKind::Permission => "permission denied",
+            ErrorKind::Quota => "quota exceeded",
+            ErrorKind::NotFound => "not found",
+            ErrorKind::Parse => "parse error",
+            ErrorKind::Network => "network error",
+            ErrorKind::Api => "API error",
+        }
+    }
+}
+
+/// Error returned by every operation of the crate.
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    message: String,
+    status: Option<u16>,
+    reason: Option<String>,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Error {
+            kind,
+            message: message.into(),
+            status: None,
+            reason: None,
+        }
+    }
+
+    pub fn config(message: impl Into<String>) -> Self {
+        Error::new(ErrorKind::Config, message)
+    }
+
+    pub fn auth(message: impl Into<String>) -> Self {
+        Error::new(ErrorKind::Auth, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Error::new(ErrorKind::NotFound, message)
+    }
+
+    pub fn parse(message: impl Into<String>) -> Self {
+        Error::new(ErrorKind::Parse, message)
+    }
+
+    /// Builds the error from an unsuccessful response, reading the Google error body if there is one.
+    pub async fn from_response(resp: reqwest::Response) -> Self {
+        let status = resp.status().as_u16();
+        let body = resp.text().await.unwrap_or_default();
+        Error::from_status(status, &body)
+    }
+
+    pub fn from_status(status: u16, body: &str) -> Self {
+        let (message, reason, oauth_error) = match serde_json::from_str::<Value>(body) {
+            // Google APIs: {"error": {"code": 403, "message": "...", "status": "PERMISSION_DENIED", ...}}
+            Ok(Value::Object(data)) if data.get("error").is_some_and(Value::is_object) => {
+                let error = &data["error"];
+                let reason = error["details"]
+                    .as_array()
+                    .and_then(|details| details.iter().find_map(|d| d["reason"].as_str()))
+                    .or_else(|| error["errors"][0]["reason"].as_str())
+                    .or_else(|| error["status"].as_str())
+                    // Microsoft Graph: {"error": {"code": "ItemNotFound", "message": "..."}}
+                    .or_else(|| error["code"].as_str())
+                    .map(str::to_string);
+                let message = error["message"].as_str().unwrap_or(body).to_string();
+                (message, reason, false)
+            }
+            // OAuth endpoints: {"error": "invalid_grant", "error_description": "..."}
+            Ok(Value::Object(data)) if data.get("error").is_some_and(Value::is_string) => {
+                let reason = data["error"].as_str().map(str::to_string);
+                let message = data
+                    .get("error_description")
+                    .and_then(Value::as_str)
+                    .or(reason.as_deref())
+                    .unwrap_or(body)
+                    .to_string();
+                (message, reason, true)
+            }
+            // Canvas and other REST APIs: {"errors": [{"message": "..."}]}
+            Ok(Value::Object(data)) if data.get("errors").is_some_and(Value::is_array) => {
+                let message = data["errors"][0]["message"].as_str().unwrap_or(body).to_string();
+                (message, None, false)
+            }
+            _ => (body.trim().to_string(), None, false),
+        };
+
+        let reason_lower = reason.as_deref().unwrap_or("").to_lowercase();
+        let kind = match status {
+            429 => ErrorKind::Quota,
+            403 if reason_lower.contains("rate") || reason_lower.contains("quota") => ErrorKind::Quota,
+            401 => ErrorKind::Auth,
+            400 if oauth_error => ErrorKind::Auth,
+            403 => ErrorKind::Permission,
+            404 => ErrorKind::NotFound,
+            _ => ErrorKind::Api,
+        };
+
+        Error {
+            kind,
+            message,
+            status: Some(status),
+            reason,
+        }
+    }
+
+    /// Prefixes the message with what was being done when the error happened.
+    pub fn context(mut self, context: impl fmt::Display) -> Self {
+        self.message = format!("{}: {}", context, self.message);
+        self
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// HTTP status of the failed response.
+    pub fn status(&self) -> Option<u16> {
+        self.status
+    }
+
+    /// Reason reported by Google, e.g. `RATE_LIMIT_EXCEEDED` or `invalid_grant`.
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        self.kind.exit_code()
+    }
+
+    /// Suggestion on how to fix the error, printed to the job log.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self.kind {
+            ErrorKind::Config => Some("check the inputs of the action step"),
+            ErrorKind::Auth => Some("check the credentials (service account email and private key, or API token) and that they were not revoked"),
+            ErrorKind::Permission => Some("share the spreadsheet with the service account email as an editor"),
+            ErrorKind::Quota => Some("too many requests, rerun the job later"),
+            ErrorKind::NotFound => Some("check the table id, the sheet name and the 'github_id' header (or the roster)"),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind.description())?;
+        match (self.status, &self.reason) {
+            (Some(status), Some(reason)) => write!(f, " (HTTP {}, {})", status, reason)?,
+            (Some(status), None) => write!(f, " (HTTP {})", status)?,
+            (None, Some(reason)) => write!(f, " ({})", reason)?,
+            (None, None) => {}
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_decode() {
+            return Error::parse(format!("unexpected response: {}", err));
+        }
+        let mut error = Error::new(ErrorKind::Network, err.to_string());
+        error.status = err.status().map(|status| status.as_u16());
+        error
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::parse(err.to_string())
+    }
+}
+
+impl From<base64::DecodeError> for Error {
+    fn from(err: base64::DecodeError) -> Self {
+        Error::parse(format!("invalid base64: {}", err))
+    }
+}
+
+impl From<std::string::FromUtf8Error> for Error {
+    fn from(err: std::string::FromUtf8Error) -> Self {
+        Error::parse(format!("invalid UTF-8: {}", err))
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for Error {
+    fn from(err: jsonwebtoken::errors::Error) -> Self {
+        Error::auth(format!("can't sign the token: {}", err))
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Self {
+        // Only a path that is not a database is a configuration problem, a busy or locked
+        // database, I/O errors and constraint violations are failures of the store
+        let kind = match err.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::CannotOpen) | Some(rusqlite::ErrorCode::NotADatabase) => ErrorKind::Config,
+            _ => ErrorKind::Api,
+        };
+        Error::new(kind, format!("database error: {}", err))
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<tokio_postgres::Error> for Error {
+    fn from(err: tokio_postgres::Error) -> Self {
+        // Errors without a server response are connection problems
+        match err.as_db_error() {
+            Some(db_error) => Error::new(postgres_error_kind(db_error.code()), format!("database error: {}", db_error)),
+            None => {
+                let cause = std::error::Error::source(&err)
+                    .map(|source| format!(" ({})", source))
+                    .unwrap_or_default();
+                Error::new(ErrorKind::Network, format!("database connection failed: {}{}", err, cause))
+            }
+        }
+    }
+}
+
+// Rejected credentials and a missing database are set up wrong, constraint violations,
+// serialization failures, deadlocks and full disks are failures of the store
+#[cfg(feature = "postgres")]
+fn postgres_error_kind(code: &tokio_postgres::error::SqlState) -> ErrorKind {
+    use tokio_postgres::error::SqlState;
+    if *code == SqlState::INVALID_PASSWORD || *code == SqlState::INVALID_AUTHORIZATION_SPECIFICATION {
+        ErrorKind::Auth
+    } else if *code == SqlState::INVALID_CATALOG_NAME {
+        ErrorKind::Config
+    } else {
+        ErrorKind::Api
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<deadpool_postgres::PoolError> for Error {
+    fn from(err: deadpool_postgres::PoolError) -> Self {
+        match err {
+            deadpool_postgres::PoolError::Backend(err) => Error::from(err),
+            other => Error::new(ErrorKind::Network, format!("database connection failed: {}", other)),
+        }
+    }
+}
+
+impl From<std::time::SystemTimeError> for Error {
+    fn from(err: std::time::SystemTimeError) -> Self {
+        Error::auth(format!("system clock is before the Unix epoch: {}", err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sqlite_error(code: std::os::raw::c_int) -> Error {
+        Error::from(rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(code), None))
+    }
+
+    #[test]
+    fn reads_the_reason_of_the_api_errors() {
+        let err = Error::from_status(403, r#"{"error": {"code": 403, "message": "No access", "status": "PERMISSION_DENIED"}}"#);
+        assert_eq!((err.kind(), err.reason()), (ErrorKind::Permission, Some("PERMISSION_DENIED")));
+        let err = Error::from_status(400, r#"{"error": "invalid_grant", "error_description": "Token has been expired"}"#);
+        assert_eq!((err.kind(), err.reason()), (ErrorKind::Auth, Some("invalid_grant")));
+        assert!(err.to_string().contains("Token has been expired"));
+        // Without a description the error code is the message
+        let err = Error::from_status(500, r#"{"error": "busy"}"#);
+        assert_eq!((err.kind(), err.reason()), (ErrorKind::Api, Some("busy")));
+        let err = Error::from_status(404, r#"{"errors": [{"message": "The specified resource does not exist."}]}"#);
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(Error::from_status(502, "<html>Bad gateway</html>").kind(), ErrorKind::Api);
+    }
+
+    #[test]
+    fn sqlite_errors_are_configuration_errors_only_for_bad_paths() {
+        assert_eq!(sqlite_error(rusqlite::ffi::SQLITE_CANTOPEN).kind(), ErrorKind::Config);
+        assert_eq!(sqlite_error(rusqlite::ffi::SQLITE_NOTADB).kind(), ErrorKind::Config);
+        assert_eq!(sqlite_error(rusqlite::ffi::SQLITE_BUSY).kind(), ErrorKind::Api);
+        assert_eq!(sqlite_error(rusqlite::ffi::SQLITE_IOERR).kind(), ErrorKind::Api);
+        assert_eq!(sqlite_error(rusqlite::ffi::SQLITE_CONSTRAINT).kind(), ErrorKind::Api);
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn postgres_errors_are_mapped_by_sql_state() {
+        use tokio_postgres::error::SqlState;
+        assert_eq!(postgres_error_kind(&SqlState::INVALID_PASSWORD), ErrorKind::Auth);
+        assert_eq!(postgres_error_kind(&SqlState::INVALID_AUTHORIZATION_SPECIFICATION), ErrorKind::Auth);
+        assert_eq!(postgres_error_kind(&SqlState::INVALID_CATALOG_NAME), ErrorKind::Config);
+        assert_eq!(postgres_error_kind(&SqlState::UNIQUE_VIOLATION), ErrorKind::Api);
+        assert_eq!(postgres_error_kind(&SqlState::T_R_SERIALIZATION_FAILURE), ErrorKind::Api);
+        assert_eq!(postgres_error_kind(&SqlState::T_R_DEADLOCK_DETECTED), ErrorKind::Api);
+        assert_eq!(postgres_error_kind(&SqlState::DISK_FULL), ErrorKind::Api);
+    }
+}
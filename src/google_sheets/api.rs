@@ -1,47 +1,82 @@
-use reqwest::Client;
+use crate::data_processing::utils::sheet_range;
+use crate::error::{Error, Result};
+use crate::google_sheets::token_provider::TokenProvider;
+use crate::http::client::{percent_encode, ApiClient};
+use reqwest::{RequestBuilder, Response, StatusCode};
 use serde_json::Value;
 
+// Sends the request with a token of the provider. If the rate limit is hit and the provider
+// has another account, the request is repeated with its token. A rejected token (revoked before
+// it expired) is dropped from the cache and the request is repeated once with a new one.
+pub(crate) async fn send(tokens: &dyn TokenProvider, request: RequestBuilder) -> Result<Response> {
+    let mut tried_tokens = Vec::new();
+    let mut renewed = false;
+    loop {
+        let token = tokens.access_token().await?;
+        let attempt = request
+            .try_clone()
+            .ok_or_else(|| Error::config("Request body can't be sent twice"))?;
+        let resp = attempt.bearer_auth(&token).send().await?;
+
+        if resp.status() == StatusCode::TOO_MANY_REQUESTS
+            && !tried_tokens.contains(&token)
+            && tokens.report_quota_exceeded(&token)
+        {
+            tried_tokens.push(token);
+            continue;
+        }
+        if resp.status() == StatusCode::UNAUTHORIZED && !renewed && tokens.report_unauthorized(&token).await {
+            renewed = true;
+            continue;
+        }
+        return Ok(resp);
+    }
+}
+
+// Sheet names may contain spaces, `#` and other characters that are not allowed in a URL path
+fn encode_range(range: &str) -> String {
+    percent_encode(range, b"!:$")
+}
+
+/// Reads the values of `range` (in A1 notation) as the raw Sheets API response.
 pub async fn read_from_sheet(
-    client: &Client,
-    access_token: &str,
+    client: &ApiClient,
+    tokens: &dyn TokenProvider,
     spreadsheet_id: &str,
     range: &str,
-) -> Result<Value, Box<dyn std::error::Error>> {
+) -> Result<Value> {
     // URL froming
-    let url = format!(
-        "https://sheets.googleapis.com/v4/spreadsheets/{}/values/{}",
-        spreadsheet_id, range
-    );
+    let url = client.sheets_url(&format!(
+        "/spreadsheets/{}/values/{}",
+        spreadsheet_id,
+        encode_range(range)
+    ));
 
     // Sending request
-    let resp = client
-        .get(&url)
-        .bearer_auth(access_token)
-        .send()
-        .await?;
+    let resp = send(tokens, client.http.get(&url)).await?;
 
     if resp.status().is_success() {
         let data: Value = resp.json().await?;
         Ok(data)
     } else {
-        let error_text = resp.text().await?;
-        println!("Error while loading data: {}", error_text);
-        Err(Box::from(error_text))
+        Err(Error::from_response(resp).await.context("Can't load data"))
     }
 }
 
+/// Writes rows of values into `range` without parsing them.
 pub async fn write_to_sheet(
-    client: &Client,
-    access_token: &str,
+    client: &ApiClient,
+    tokens: &dyn TokenProvider,
     spreadsheet_id: &str,
     range: &str,
     values: &Vec<Vec<String>>,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<()> {
     // URL froming
-    let url = format!(
-        "https://sheets.googleapis.com/v4/spreadsheets/{}/values/{}?valueInputOption=RAW",
-        spreadsheet_id, range
-    );
+    let url = client.sheets_url(&format!(
+        "/spreadsheets/{}/values/{}?valueInputOption=RAW",
+        spreadsheet_id,
+        encode_range(range)
+    ));
 
     // Request body
     let body = serde_json::json!({
@@ -51,112 +86,171 @@ pub async fn write_to_sheet(
     });
 
     // Sending request
-    let resp = client
-        .put(&url)
-        .bearer_auth(access_token)
-        .json(&body)
-        .send()
-        .await?;
+    let resp = send(tokens, client.http.put(&url).json(&body)).await?;
 
     if resp.status().is_success() {
         let _: Value = resp.json().await?;
         Ok(())
     } else {
-        let error_text = resp.text().await?;
-        println!("Writing error: {}", error_text);
-        Err(Box::from(error_text))
+        Err(Error::from_response(resp).await.context("Can't write data"))
     }
 }
 
 
+/// Writes one value into the cell of the sheet, growing the sheet if it is too small.
 pub async fn write_to_cell(
-    client: &Client,
-    access_token: &str,
+    client: &ApiClient,
+    tokens: &dyn TokenProvider,
     spreadsheet_id: &str,
+    sheet_name: &str,
     row: usize,
     col: usize,
     value: Value, 
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<()> {
     // Transforming coordinates to cell address
     let cell_address = crate::data_processing::utils::coords_to_cell_address(row, col);
-    let range = cell_address.clone();
+    let range = sheet_range(sheet_name, &cell_address);
+
+    // Check and resize the table if necessary
+    let sheet_id = get_sheet_id_by_name(client, tokens, spreadsheet_id, sheet_name).await?;
+    ensure_sheet_size(client, tokens, spreadsheet_id, sheet_id, row + 1, col + 1).await?;
+
+    // URL
+    let url = client.sheets_url(&format!(
+        "/spreadsheets/{}/values/{}?valueInputOption=USER_ENTERED",
+        spreadsheet_id,
+        encode_range(&range)
+    ));
+
+    // Request body
+    let body = serde_json::json!({
+        "range": range,
+        "majorDimension": "ROWS",
+        "values": [[value]]
+    });
+    
+    // Sending request
+    let resp = send(tokens, client.http.put(&url).json(&body)).await?;
+
+    if resp.status().is_success() {
+        let _: Value = resp.json().await?;
+        Ok(())
+    } else {
+        Err(Error::from_response(resp).await.context(format!("Can't write data {}", range)))
+    }
+}
+
+/// Writes several cells of the sheet in one request, growing the sheet if it is too small.
+pub async fn write_to_cells(
+    client: &ApiClient,
+    tokens: &dyn TokenProvider,
+    spreadsheet_id: &str,
+    sheet_name: &str,
+    cells: &[(usize, usize, Value)],
+) -> Result<()> {
+    if cells.is_empty() {
+        return Ok(());
+    }
 
     // Check and resize the table if necessary
-    let sheet_id = 0; // Assuming that working with the first sheet
-    let required_column_count = col + 1;
-    let required_row_count = row + 1;
+    let sheet_id = get_sheet_id_by_name(client, tokens, spreadsheet_id, sheet_name).await?;
+    let required_row_count = cells.iter().map(|(row, _, _)| row + 1).max().unwrap_or(0);
+    let required_column_count = cells.iter().map(|(_, col, _)| col + 1).max().unwrap_or(0);
+    ensure_sheet_size(
+        client,
+        tokens,
+        spreadsheet_id,
+        sheet_id,
+        required_row_count,
+        required_column_count,
+    )
+        .await?;
 
+    // URL
+    let url = client.sheets_url(&format!(
+        "/spreadsheets/{}/values:batchUpdate",
+        spreadsheet_id
+    ));
+
+    // Request body, one range per cell
+    let data: Vec<Value> = cells
+        .iter()
+        .map(|(row, col, value)| {
+            serde_json::json!({
+                "range": sheet_range(sheet_name, &crate::data_processing::utils::coords_to_cell_address(*row, *col)),
+                "majorDimension": "ROWS",
+                "values": [[value]]
+            })
+        })
+        .collect();
+    let body = serde_json::json!({
+        "valueInputOption": "USER_ENTERED",
+        "data": data
+    });
+
+    // Sending request
+    let resp = send(tokens, client.http.post(&url).json(&body)).await?;
+
+    if resp.status().is_success() {
+        let _: Value = resp.json().await?;
+        Ok(())
+    } else {
+        Err(Error::from_response(resp).await.context(format!("Can't write {} cells", cells.len())))
+    }
+}
+
+// Enlarges the sheet so that it has at least the given number of rows and columns
+async fn ensure_sheet_size(
+    client: &ApiClient,
+    tokens: &dyn TokenProvider,
+    spreadsheet_id: &str,
+    sheet_id: u32,
+    required_row_count: usize,
+    required_column_count: usize,
+) -> Result<()> {
     // Get current table size
     let (current_row_count, current_column_count) =
-        get_sheet_dimensions(client, access_token, spreadsheet_id, sheet_id).await?;
+        get_sheet_dimensions(client, tokens, spreadsheet_id, sheet_id).await?;
 
     // Enlarge the number of columns if necessary
     if required_column_count > current_column_count {
-        crate::google_sheets::api::expand_sheet_columns(
+        expand_sheet_columns(
             client,
-            access_token,
+            tokens,
             spreadsheet_id,
             sheet_id,
             required_column_count as u32,
         )
             .await?;
     }
-    
+
     // Enlarge the number of rows if necessary
     if required_row_count > current_row_count {
         expand_sheet_rows(
             client,
-            access_token,
+            tokens,
             spreadsheet_id,
             sheet_id,
             required_row_count as u32,
         )
             .await?;
     }
-
-    // URL
-    let url = format!(
-        "https://sheets.googleapis.com/v4/spreadsheets/{}/values/{}?valueInputOption=USER_ENTERED",
-        spreadsheet_id, range
-    );
-
-    // Request body
-    let body = serde_json::json!({
-        "range": range,
-        "majorDimension": "ROWS",
-        "values": [[value]]
-    });
-    
-    // Sending request
-    let resp = client
-        .put(&url)
-        .bearer_auth(access_token)
-        .json(&body)
-        .send()
-        .await?;
-
-    if resp.status().is_success() {
-        let _: Value = resp.json().await?;
-        Ok(())
-    } else {
-        let error_text = resp.text().await?;
-        println!("Error while writing data {}: {}", cell_address, error_text);
-        Err(Box::from(error_text))
-    }
+    Ok(())
 }
 
+/// Sets the number of columns of the sheet.
 pub async fn expand_sheet_columns(
-    client: &Client,
-    access_token: &str,
+    client: &ApiClient,
+    tokens: &dyn TokenProvider,
     spreadsheet_id: &str,
     sheet_id: u32,
     new_column_count: u32,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<()> {
     // URL
-    let url = format!(
-        "https://sheets.googleapis.com/v4/spreadsheets/{}:batchUpdate",
+    let url = client.sheets_url(&format!(
+        "/spreadsheets/{}:batchUpdate",
         spreadsheet_id
-    );
+    ));
 
     // Request body
     let body = serde_json::json!({
@@ -176,41 +270,31 @@ pub async fn expand_sheet_columns(
     });
 
     // Sending request
-    let resp = client
-        .post(&url)
-        .bearer_auth(access_token)
-        .json(&body)
-        .send()
-        .await?;
+    let resp = send(tokens, client.http.post(&url).json(&body)).await?;
 
     if resp.status().is_success() {
         let _: Value = resp.json().await?;
         Ok(())
     } else {
-        let error_text = resp.text().await?;
-        println!("Error while expanding the table: {}", error_text);
-        Err(Box::from(error_text))
+        Err(Error::from_response(resp).await.context("Can't expand the table"))
     }
 }
 
+/// Looks up the numeric id of the sheet with the given title.
 pub async fn get_sheet_id_by_name(
-    client: &Client,
-    access_token: &str,
+    client: &ApiClient,
+    tokens: &dyn TokenProvider,
     spreadsheet_id: &str,
     sheet_name: &str,
-) -> Result<u32, Box<dyn std::error::Error>> {
+) -> Result<u32> {
     // URL
-    let url = format!(
-        "https://sheets.googleapis.com/v4/spreadsheets/{}",
+    let url = client.sheets_url(&format!(
+        "/spreadsheets/{}",
         spreadsheet_id
-    );
+    ));
 
     // Sending request
-    let resp = client
-        .get(&url)
-        .bearer_auth(access_token)
-        .send()
-        .await?;
+    let resp = send(tokens, client.http.get(&url)).await?;
 
     if resp.status().is_success() {
         let data: Value = resp.json().await?;
@@ -226,32 +310,27 @@ pub async fn get_sheet_id_by_name(
                 }
             }
         }
-        Err("Can't find the sheet".into())
+        Err(Error::not_found(format!("Can't find the sheet '{}'", sheet_name)))
     } else {
-        let error_text = resp.text().await?;
-        println!("Error while loading table info {}", error_text);
-        Err(Box::from(error_text))
+        Err(Error::from_response(resp).await.context("Can't load table info"))
     }
 }
 
+/// Returns the number of rows and columns of the sheet.
 pub async fn get_sheet_dimensions(
-    client: &Client,
-    access_token: &str,
+    client: &ApiClient,
+    tokens: &dyn TokenProvider,
     spreadsheet_id: &str,
     sheet_id: u32,
-) -> Result<(usize, usize), Box<dyn std::error::Error>> {
+) -> Result<(usize, usize)> {
     // URL
-    let url = format!(
-        "https://sheets.googleapis.com/v4/spreadsheets/{}?fields=sheets.properties",
+    let url = client.sheets_url(&format!(
+        "/spreadsheets/{}?fields=sheets.properties",
         spreadsheet_id
-    );
+    ));
 
     // Sending request
-    let resp = client
-        .get(&url)
-        .bearer_auth(access_token)
-        .send()
-        .await?;
+    let resp = send(tokens, client.http.get(&url)).await?;
 
     if resp.status().is_success() {
         let data: Value = resp.json().await?;
@@ -274,27 +353,26 @@ pub async fn get_sheet_dimensions(
                 }
             }
         }
-        Err("Can't get size of the sheet".into())
+        Err(Error::not_found(format!("Can't get size of the sheet {}", sheet_id)))
     } else {
-        let error_text = resp.text().await?;
-        println!("Error while loading size of the sheet {}", error_text);
-        Err(Box::from(error_text))
+        Err(Error::from_response(resp).await.context("Can't load size of the sheet"))
     }
 }
 
 
+/// Sets the number of rows of the sheet.
 pub async fn expand_sheet_rows(
-    client: &Client,
-    access_token: &str,
+    client: &ApiClient,
+    tokens: &dyn TokenProvider,
     spreadsheet_id: &str,
     sheet_id: u32,
     new_row_count: u32,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<()> {
     // URL
-    let url = format!(
-        "https://sheets.googleapis.com/v4/spreadsheets/{}:batchUpdate",
+    let url = client.sheets_url(&format!(
+        "/spreadsheets/{}:batchUpdate",
         spreadsheet_id
-    );
+    ));
 
     // Request body
     let body = serde_json::json!({
@@ -314,20 +392,13 @@ pub async fn expand_sheet_rows(
     });
 
     // Sending request
-    let resp = client
-        .post(&url)
-        .bearer_auth(access_token)
-        .json(&body)
-        .send()
-        .await?;
+    let resp = send(tokens, client.http.post(&url).json(&body)).await?;
 
     if resp.status().is_success() {
         let _: Value = resp.json().await?;
         Ok(())
     } else {
-        let error_text = resp.text().await?;
-        println!("Error while enlarging amount of rows: {}", error_text);
-        Err(Box::from(error_text))
+        Err(Error::from_response(resp).await.context("Can't enlarge amount of rows"))
     }
 }
 
@@ -1,2 +1,5 @@
 pub mod auth;
 pub mod api;
+pub mod oauth;
+pub mod token_provider;
+pub mod workload_identity;
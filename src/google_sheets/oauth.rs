@@ -0,0 +1,405 @@
+use crate::error::{Error, Result};
+use crate::google_sheets::token_provider::{write_private_file, AccessToken, TokenSource};
+use crate::http::client::ApiClient;
+use async_trait::async_trait;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+// How long the sign-in waits for the browser, a run without anyone to sign in fails instead of hanging
+const SIGN_IN_TIMEOUT: Duration = Duration::from_secs(300);
+
+// Browsers open connections ahead of time and may never send a request on them
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// OAuth client of the "Desktop app" type, created in the Google Cloud Console.
+#[derive(Debug, Clone)]
+pub struct OAuthClientConfig {
+    pub client_id: String,
+    /// Google issues a secret for desktop clients too, it is not confidential there.
+    pub client_secret: Option<String>,
+    /// Directory where the refresh token is stored.
+    pub credentials_dir: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredCredentials {
+    client_id: String,
+    refresh_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OAuthTokenResponse {
+    access_token: String,
+    expires_in: u64,
+    refresh_token: Option<String>,
+}
+
+impl OAuthClientConfig {
+    pub fn new(client_id: &str, client_secret: Option<&str>) -> Result<Self> {
+        Ok(OAuthClientConfig {
+            client_id: client_id.to_string(),
+            client_secret: client_secret.map(str::to_string),
+            credentials_dir: default_credentials_dir()?,
+        })
+    }
+
+    fn credentials_file(&self) -> PathBuf {
+        let client: String = self
+            .client_id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+            .collect();
+        self.credentials_dir.join(format!("oauth-{}.json", client))
+    }
+}
+
+// $XDG_CONFIG_HOME (or ~/.config) on Unix, %APPDATA% on Windows
+fn default_credentials_dir() -> Result<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .ok_or_else(|| Error::config("Can't find the config directory, set XDG_CONFIG_HOME"))?;
+    Ok(base.join("classroom-to-sheets"))
+}
+
+/// Token source of a Google user who signed in through the browser.
+pub struct OAuthTokenSource {
+    client: ApiClient,
+    config: OAuthClientConfig,
+    scope: String,
+    refresh_token: String,
+}
+
+impl OAuthTokenSource {
+    /// Uses the stored refresh token, or asks the user to sign in if there is none.
+    pub async fn load_or_authorize(
+        client: ApiClient,
+        config: OAuthClientConfig,
+        scope: &str,
+    ) -> Result<Self> {
+        let refresh_token = match read_stored_credentials(&config) {
+            Some(credentials) => credentials.refresh_token,
+            None => {
+                let refresh_token = authorize(&client, &config, scope).await?;
+                store_credentials(&config, &refresh_token)?;
+                refresh_token
+            }
+        };
+
+        Ok(OAuthTokenSource {
+            client,
+            config,
+            scope: scope.to_string(),
+            refresh_token,
+        })
+    }
+}
+
+#[async_trait]
+impl TokenSource for OAuthTokenSource {
+    async fn fetch_token(&self) -> Result<AccessToken> {
+        let mut params = vec![
+            ("grant_type", "refresh_token"),
+            ("refresh_token", self.refresh_token.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+        ];
+        if let Some(client_secret) = &self.config.client_secret {
+            params.push(("client_secret", client_secret));
+        }
+
+        let resp = self
+            .client
+            .http
+            .post(&self.client.endpoints.token_url)
+            .form(&params)
+            .send()
+            .await?;
+
+        if resp.status().is_success() {
+            let token: OAuthTokenResponse = resp.json().await?;
+            AccessToken::new(token.access_token, token.expires_in)
+        } else {
+            Err(Error::from_response(resp).await.context(format!(
+                "Can't refresh the sign-in, delete {} and sign in again",
+                self.config.credentials_file().display()
+            )))
+        }
+    }
+
+    fn cache_key(&self) -> String {
+        format!("oauth-{}-{}", self.config.client_id, self.scope)
+    }
+}
+
+fn read_stored_credentials(config: &OAuthClientConfig) -> Option<StoredCredentials> {
+    let content = fs::read_to_string(config.credentials_file()).ok()?;
+    let credentials: StoredCredentials = serde_json::from_str(&content).ok()?;
+    Some(credentials).filter(|credentials| credentials.client_id == config.client_id)
+}
+
+fn store_credentials(config: &OAuthClientConfig, refresh_token: &str) -> Result<()> {
+    let path = config.credentials_file();
+    let credentials = StoredCredentials {
+        client_id: config.client_id.clone(),
+        refresh_token: refresh_token.to_string(),
+    };
+    // The refresh token gives access to the user's sheets, keep it private
+    let write = || -> std::io::Result<()> {
+        let mut dir = fs::DirBuilder::new();
+        dir.recursive(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+            dir.mode(0o700);
+            dir.create(&config.credentials_dir)?;
+            fs::set_permissions(&config.credentials_dir, fs::Permissions::from_mode(0o700))?;
+        }
+        #[cfg(not(unix))]
+        dir.create(&config.credentials_dir)?;
+        write_private_file(&path, &serde_json::to_string_pretty(&credentials)?)
+    };
+    write().map_err(|e| Error::config(format!("Can't store the sign-in in {}: {}", path.display(), e)))
+}
+
+fn random_url_safe(bytes: usize) -> Result<String> {
+    let mut buf = vec![0u8; bytes];
+    openssl::rand::rand_bytes(&mut buf)
+        .map_err(|e| Error::auth(format!("Can't generate random data: {}", e)))?;
+    Ok(base64::encode_config(buf, base64::URL_SAFE_NO_PAD))
+}
+
+/// Runs the authorization code flow with PKCE and returns the refresh token.
+pub async fn authorize(client: &ApiClient, config: &OAuthClientConfig, scope: &str) -> Result<String> {
+    // PKCE verifier and its S256 challenge
+    let code_verifier = random_url_safe(32)?;
+    let code_challenge = base64::encode_config(
+        openssl::sha::sha256(code_verifier.as_bytes()),
+        base64::URL_SAFE_NO_PAD,
+    );
+    let state = random_url_safe(16)?;
+
+    // Loopback listener receiving the redirect
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .map_err(|e| Error::config(format!("Can't listen for the OAuth redirect: {}", e)))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| Error::config(format!("Can't listen for the OAuth redirect: {}", e)))?
+        .port();
+    let redirect_uri = format!("http://127.0.0.1:{}", port);
+
+    let auth_url = Url::parse_with_params(
+        &client.endpoints.auth_url,
+        &[
+            ("client_id", config.client_id.as_str()),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("response_type", "code"),
+            ("scope", scope),
+            ("code_challenge", code_challenge.as_str()),
+            ("code_challenge_method", "S256"),
+            ("state", state.as_str()),
+            ("access_type", "offline"),
+            ("prompt", "consent"),
+        ],
+    )
+        .map_err(|e| Error::config(format!("Invalid OAuth authorization URL: {}", e)))?;
+
+    eprintln!("Open this URL in your browser to sign in:\n\n{}\n", auth_url);
+    open_browser(auth_url.as_str());
+
+    let code = receive_code(&listener, &state, SIGN_IN_TIMEOUT).await?;
+
+    // Exchange the code for the tokens
+    let mut params = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code.as_str()),
+        ("redirect_uri", redirect_uri.as_str()),
+        ("client_id", config.client_id.as_str()),
+        ("code_verifier", code_verifier.as_str()),
+    ];
+    if let Some(client_secret) = &config.client_secret {
+        params.push(("client_secret", client_secret));
+    }
+
+    let resp = client
+        .http
+        .post(&client.endpoints.token_url)
+        .form(&params)
+        .send()
+        .await?;
+
+    if resp.status().is_success() {
+        let token: OAuthTokenResponse = resp.json().await?;
+        token
+            .refresh_token
+            .ok_or_else(|| Error::auth("Google did not return a refresh token, revoke the app access and sign in again"))
+    } else {
+        Err(Error::from_response(resp).await.context("Can't exchange the authorization code"))
+    }
+}
+
+// Waits for the browser to be redirected back with the authorization code
+async fn receive_code(listener: &TcpListener, state: &str, timeout: Duration) -> Result<String> {
+    tokio::time::timeout(timeout, accept_redirect(listener, state))
+        .await
+        .map_err(|_| {
+            Error::auth(format!(
+                "Nobody signed in within {} seconds, run the sign-in where a browser is available",
+                timeout.as_secs()
+            ))
+        })?
+}
+
+async fn accept_redirect(listener: &TcpListener, state: &str) -> Result<String> {
+    loop {
+        let (mut stream, _) = listener
+            .accept()
+            .await
+            .map_err(|e| Error::auth(format!("Can't receive the OAuth redirect: {}", e)))?;
+
+        let path = match tokio::time::timeout(REQUEST_TIMEOUT, read_request_path(&mut stream)).await {
+            Ok(Some(path)) => path,
+            // An idle or broken connection, wait for the next one
+            _ => continue,
+        };
+        let url = Url::parse(&format!("http://127.0.0.1{}", path))
+            .map_err(|e| Error::auth(format!("Invalid OAuth redirect: {}", e)))?;
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+
+        // Browsers also ask for the favicon, ignore such requests
+        if param("state").is_none() {
+            respond(&mut stream, "404 Not Found", "").await;
+            continue;
+        }
+        if param("state").as_deref() != Some(state) {
+            respond(&mut stream, "400 Bad Request", "Sign-in failed, the state does not match.").await;
+            return Err(Error::auth("OAuth state mismatch, the redirect did not come from this sign-in"));
+        }
+        if let Some(error) = param("error") {
+            respond(&mut stream, "200 OK", "Sign-in was cancelled, you can close this window.").await;
+            return Err(Error::auth(format!("Sign-in failed: {}", error)));
+        }
+        if let Some(code) = param("code") {
+            respond(&mut stream, "200 OK", "Signed in, you can close this window.").await;
+            return Ok(code);
+        }
+        respond(&mut stream, "400 Bad Request", "No authorization code in the redirect.").await;
+    }
+}
+
+// Path of the request line, after reading the request up to the end of its headers
+async fn read_request_path(stream: &mut TcpStream) -> Option<String> {
+    let mut reader = BufReader::new(stream).take(16 * 1024);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await.ok()?;
+    let path = request_line.split_whitespace().nth(1)?.to_string();
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line).await {
+            Ok(0) => return None,
+            Ok(_) if line.trim_end().is_empty() => return Some(path),
+            Ok(_) => {}
+            Err(_) => return None,
+        }
+    }
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    // The browser may have gone away, the flow does not depend on it
+    let _ = stream.write_all(response.as_bytes()).await;
+}
+
+fn open_browser(url: &str) {
+    let command = if cfg!(target_os = "macos") {
+        std::process::Command::new("open").arg(url).spawn()
+    } else if cfg!(windows) {
+        std::process::Command::new("rundll32").args(["url.dll,FileProtocolHandler", url]).spawn()
+    } else {
+        std::process::Command::new("xdg-open").arg(url).spawn()
+    };
+    // Without a browser the user opens the printed URL manually
+    let _ = command;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(dir: &Path) -> OAuthClientConfig {
+        OAuthClientConfig {
+            client_id: "desktop.apps.googleusercontent.com".to_string(),
+            client_secret: None,
+            credentials_dir: dir.join("classroom-to-sheets"),
+        }
+    }
+
+    #[test]
+    fn stores_the_refresh_token_privately() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path());
+        store_credentials(&config, "refresh-1").unwrap();
+        // Replacing the sign-in keeps the file private too
+        store_credentials(&config, "refresh-2").unwrap();
+
+        assert_eq!(read_stored_credentials(&config).unwrap().refresh_token, "refresh-2");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(&config.credentials_file()), 0o600);
+            assert_eq!(mode(&config.credentials_dir), 0o700);
+        }
+    }
+
+    #[tokio::test]
+    async fn receives_a_redirect_sent_in_pieces() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let browser = tokio::spawn(async move {
+            let mut favicon = TcpStream::connect(address).await.unwrap();
+            favicon.write_all(b"GET /favicon.ico HTTP/1.1\r\nHost: localhost\r\n\r\n").await.unwrap();
+            let mut response = String::new();
+            favicon.read_to_string(&mut response).await.unwrap();
+            assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+
+            let mut redirect = TcpStream::connect(address).await.unwrap();
+            for piece in ["GET /?state=xyz&co", "de=4%2F0Ab HTTP/1.1\r\nHost: local", "host\r\n\r\n"] {
+                redirect.write_all(piece.as_bytes()).await.unwrap();
+                redirect.flush().await.unwrap();
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            let mut response = String::new();
+            redirect.read_to_string(&mut response).await.unwrap();
+            response
+        });
+
+        let code = receive_code(&listener, "xyz", Duration::from_secs(10)).await.unwrap();
+        assert_eq!(code, "4/0Ab");
+        assert!(browser.await.unwrap().starts_with("HTTP/1.1 200 OK"));
+    }
+
+    #[tokio::test]
+    async fn gives_up_when_nobody_signs_in() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let err = receive_code(&listener, "xyz", Duration::from_millis(50)).await.unwrap_err();
+        assert_eq!(err.kind(), crate::error::ErrorKind::Auth);
+    }
+}
@@ -0,0 +1,513 @@
+use crate::error::{Error, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex as StdMutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+// Tokens are refreshed this many seconds before they expire
+pub const DEFAULT_REFRESH_MARGIN: u64 = 300;
+
+/// Access token together with the moment it expires.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessToken {
+    pub token: String,
+    /// Expiry time in seconds since the Unix epoch.
+    pub expires_at: u64,
+}
+
+impl AccessToken {
+    pub fn new(token: String, expires_in: u64) -> Result<Self> {
+        Ok(AccessToken {
+            token,
+            expires_at: now()? + expires_in,
+        })
+    }
+
+    pub fn expires_within(&self, seconds: u64) -> Result<bool> {
+        Ok(now()? + seconds >= self.expires_at)
+    }
+}
+
+fn now() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+/// Writes a file only the current user can read (a token or a key), through a temporary file
+/// that is created with these permissions, so the secret is never readable by others.
+pub(crate) fn write_private_file(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_name);
+    // A temporary file left by a crashed run may have other permissions
+    let _ = fs::remove_file(&tmp_path);
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let result = options
+        .open(&tmp_path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .and_then(|()| fs::rename(&tmp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Obtains new access tokens, e.g. by signing a JWT with a service account key.
+#[async_trait]
+pub trait TokenSource: Send + Sync {
+    async fn fetch_token(&self) -> Result<AccessToken>;
+
+    /// Identifies the account and the scope, used to name the cache file.
+    fn cache_key(&self) -> String;
+}
+
+/// Hands out a valid access token to every API call.
+#[async_trait]
+pub trait TokenProvider: Send + Sync {
+    async fn access_token(&self) -> Result<String>;
+
+    /// Called when a request made with `token` hit the rate limit.
+    /// Returns whether the request may be retried with another token.
+    fn report_quota_exceeded(&self, _token: &str) -> bool {
+        false
+    }
+
+    /// Called when `token` was rejected (HTTP 401), e.g. because it was revoked before it expired.
+    /// Returns whether the request may be retried with a new token.
+    async fn report_unauthorized(&self, _token: &str) -> bool {
+        false
+    }
+}
+
+// A token obtained elsewhere is used as is
+#[async_trait]
+impl TokenProvider for String {
+    async fn access_token(&self) -> Result<String> {
+        Ok(self.clone())
+    }
+}
+
+/// Keeps the token of a [`TokenSource`] in memory (and optionally on disk) and refreshes it ahead of expiry.
+pub struct CachedTokenProvider {
+    source: Box<dyn TokenSource>,
+    cached: Mutex<Option<AccessToken>>,
+    cache_dir: Option<PathBuf>,
+    refresh_margin: u64,
+}
+
+impl CachedTokenProvider {
+    pub fn new(source: Box<dyn TokenSource>) -> Self {
+        CachedTokenProvider {
+            source,
+            cached: Mutex::new(None),
+            cache_dir: None,
+            refresh_margin: DEFAULT_REFRESH_MARGIN,
+        }
+    }
+
+    /// Also stores the token in `cache_dir`, so it survives between runs.
+    pub fn with_cache_dir(mut self, cache_dir: PathBuf) -> Self {
+        self.cache_dir = Some(cache_dir);
+        self
+    }
+
+    pub fn with_refresh_margin(mut self, seconds: u64) -> Self {
+        self.refresh_margin = seconds;
+        self
+    }
+
+    fn cache_file(&self) -> Option<PathBuf> {
+        let key: String = self
+            .source
+            .cache_key()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+            .collect();
+        self.cache_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.json", key)))
+    }
+
+    fn read_cache_file(&self) -> Option<AccessToken> {
+        let content = fs::read_to_string(self.cache_file()?).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn write_cache_file(&self, token: &AccessToken) -> Result<()> {
+        let path = match self.cache_file() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        // The token grants access to the sheets, keep it private
+        let write = || -> std::io::Result<()> {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            write_private_file(&path, &serde_json::to_string(token)?)
+        };
+        write().map_err(|e| {
+            Error::config(format!("Can't write token cache {}: {}", path.display(), e))
+        })
+    }
+}
+
+#[async_trait]
+impl TokenProvider for CachedTokenProvider {
+    async fn access_token(&self) -> Result<String> {
+        let mut cached = self.cached.lock().await;
+
+        if let Some(token) = cached.as_ref() {
+            if !token.expires_within(self.refresh_margin)? {
+                return Ok(token.token.clone());
+            }
+        }
+
+        if let Some(token) = self.read_cache_file() {
+            if !token.expires_within(self.refresh_margin)? {
+                let access_token = token.token.clone();
+                *cached = Some(token);
+                return Ok(access_token);
+            }
+        }
+
+        let token = self.source.fetch_token().await?;
+        self.write_cache_file(&token)?;
+        let access_token = token.token.clone();
+        *cached = Some(token);
+        Ok(access_token)
+    }
+
+    // Forgets the token in memory and on disk, the next request fetches a new one
+    async fn report_unauthorized(&self, token: &str) -> bool {
+        let mut cached = self.cached.lock().await;
+        if cached.as_ref().is_some_and(|cached| cached.token == token) {
+            *cached = None;
+        }
+        if let Some(path) = self.cache_file() {
+            if self.read_cache_file().is_some_and(|cached| cached.token == token) {
+                let _ = fs::remove_file(path);
+            }
+        }
+        true
+    }
+}
+
+/// How [`ShardedTokenProvider`] spreads requests among the accounts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShardingStrategy {
+    /// Every request uses the next account.
+    RoundRobin,
+    /// One account is used until it hits the rate limit.
+    OnQuota,
+}
+
+/// Requests made with one account of a [`ShardedTokenProvider`].
+#[derive(Debug, Clone)]
+pub struct ShardUsage {
+    pub account: String,
+    pub requests: u64,
+    pub throttled: u64,
+}
+
+struct Shard {
+    provider: CachedTokenProvider,
+    account: String,
+    usage: StdMutex<ShardWindow>,
+    last_token: StdMutex<Option<String>>,
+}
+
+#[derive(Default)]
+struct ShardWindow {
+    requests: u64,
+    throttled: u64,
+    // Requests in the current minute, Sheets quotas are per minute
+    window_start: u64,
+    window_requests: u64,
+}
+
+/// Distributes requests among several accounts to multiply the per-account quota.
+pub struct ShardedTokenProvider {
+    shards: Vec<Shard>,
+    strategy: ShardingStrategy,
+    requests_per_minute: Option<u64>,
+    next: AtomicUsize,
+}
+
+impl ShardedTokenProvider {
+    pub fn new(strategy: ShardingStrategy) -> Self {
+        ShardedTokenProvider {
+            shards: Vec::new(),
+            strategy,
+            requests_per_minute: None,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Adds an account, `account` is the name shown in the usage summary.
+    pub fn with_account(mut self, account: &str, provider: CachedTokenProvider) -> Self {
+        self.shards.push(Shard {
+            provider,
+            account: account.to_string(),
+            usage: StdMutex::new(ShardWindow::default()),
+            last_token: StdMutex::new(None),
+        });
+        self
+    }
+
+    /// Skips accounts that already made this many requests in the current minute.
+    pub fn with_requests_per_minute(mut self, limit: u64) -> Self {
+        self.requests_per_minute = Some(limit);
+        self
+    }
+
+    pub fn usage(&self) -> Vec<ShardUsage> {
+        self.shards
+            .iter()
+            .map(|shard| {
+                let usage = shard.usage.lock().unwrap_or_else(|e| e.into_inner());
+                ShardUsage {
+                    account: shard.account.clone(),
+                    requests: usage.requests,
+                    throttled: usage.throttled,
+                }
+            })
+            .collect()
+    }
+
+    fn pick_shard(&self, minute: u64) -> usize {
+        let count = self.shards.len();
+        let start = match self.strategy {
+            ShardingStrategy::RoundRobin => self.next.fetch_add(1, Ordering::SeqCst),
+            ShardingStrategy::OnQuota => self.next.load(Ordering::SeqCst),
+        };
+
+        // Prefer an account that has quota left in this minute
+        (0..count)
+            .map(|offset| (start + offset) % count)
+            .find(|&index| match self.requests_per_minute {
+                Some(limit) => {
+                    let usage = self.shards[index].usage.lock().unwrap_or_else(|e| e.into_inner());
+                    usage.window_start != minute || usage.window_requests < limit
+                }
+                None => true,
+            })
+            .unwrap_or(start % count)
+    }
+}
+
+#[async_trait]
+impl TokenProvider for ShardedTokenProvider {
+    async fn access_token(&self) -> Result<String> {
+        if self.shards.is_empty() {
+            return Err(Error::config("no accounts to distribute the requests among"));
+        }
+
+        let minute = now()? / 60;
+        let shard = &self.shards[self.pick_shard(minute)];
+        let token = shard.provider.access_token().await?;
+
+        let mut usage = shard.usage.lock().unwrap_or_else(|e| e.into_inner());
+        if usage.window_start != minute {
+            usage.window_start = minute;
+            usage.window_requests = 0;
+        }
+        usage.requests += 1;
+        usage.window_requests += 1;
+        *shard.last_token.lock().unwrap_or_else(|e| e.into_inner()) = Some(token.clone());
+        Ok(token)
+    }
+
+    fn report_quota_exceeded(&self, token: &str) -> bool {
+        let count = self.shards.len();
+        let throttled = self.shards.iter().position(|shard| {
+            shard.last_token.lock().unwrap_or_else(|e| e.into_inner()).as_deref() == Some(token)
+        });
+
+        if let Some(index) = throttled {
+            self.shards[index].usage.lock().unwrap_or_else(|e| e.into_inner()).throttled += 1;
+            if self.strategy == ShardingStrategy::OnQuota {
+                self.next.store((index + 1) % count, Ordering::SeqCst);
+            }
+        }
+        // A token of no account was not rotated away from, retrying would hit the limit again
+        throttled.is_some() && count > 1
+    }
+
+    async fn report_unauthorized(&self, token: &str) -> bool {
+        let shard = self.shards.iter().find(|shard| {
+            shard.last_token.lock().unwrap_or_else(|e| e.into_inner()).as_deref() == Some(token)
+        });
+        match shard {
+            Some(shard) => shard.provider.report_unauthorized(token).await,
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU64;
+    use std::sync::Arc;
+
+    // Hands out `<name>-1`, `<name>-2`, ... valid for `expires_in` seconds
+    struct FakeSource {
+        name: String,
+        expires_in: u64,
+        fetched: Arc<AtomicU64>,
+    }
+
+    #[async_trait]
+    impl TokenSource for FakeSource {
+        async fn fetch_token(&self) -> Result<AccessToken> {
+            let count = self.fetched.fetch_add(1, Ordering::SeqCst) + 1;
+            AccessToken::new(format!("{}-{}", self.name, count), self.expires_in)
+        }
+
+        fn cache_key(&self) -> String {
+            format!("fake {}", self.name)
+        }
+    }
+
+    // The provider and the number of tokens it fetched
+    fn counted_provider(name: &str, expires_in: u64) -> (CachedTokenProvider, Arc<AtomicU64>) {
+        let fetched = Arc::new(AtomicU64::new(0));
+        let source = FakeSource {
+            name: name.to_string(),
+            expires_in,
+            fetched: fetched.clone(),
+        };
+        (CachedTokenProvider::new(Box::new(source)), fetched)
+    }
+
+    fn provider(name: &str) -> CachedTokenProvider {
+        counted_provider(name, 3600).0
+    }
+
+    #[tokio::test]
+    async fn reuses_the_token_until_it_is_about_to_expire() {
+        let (tokens, fetched) = counted_provider("a", 3600);
+        assert_eq!(tokens.access_token().await.unwrap(), "a-1");
+        assert_eq!(tokens.access_token().await.unwrap(), "a-1");
+        assert_eq!(fetched.load(Ordering::SeqCst), 1);
+
+        // Within the refresh margin of its expiry the token is replaced
+        let (tokens, fetched) = counted_provider("b", 60);
+        assert_eq!(tokens.access_token().await.unwrap(), "b-1");
+        assert_eq!(tokens.access_token().await.unwrap(), "b-2");
+        assert_eq!(fetched.load(Ordering::SeqCst), 2);
+
+        let tokens = counted_provider("c", 60).0.with_refresh_margin(0);
+        assert_eq!(tokens.access_token().await.unwrap(), "c-1");
+        assert_eq!(tokens.access_token().await.unwrap(), "c-1");
+    }
+
+    #[tokio::test]
+    async fn reuses_the_token_cached_on_disk_by_an_earlier_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, _) = counted_provider("a", 3600);
+        let first = first.with_cache_dir(dir.path().to_path_buf());
+        assert_eq!(first.access_token().await.unwrap(), "a-1");
+
+        let (second, fetched) = counted_provider("a", 3600);
+        let second = second.with_cache_dir(dir.path().to_path_buf());
+        assert_eq!(second.access_token().await.unwrap(), "a-1");
+        assert_eq!(fetched.load(Ordering::SeqCst), 0);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(second.cache_file().unwrap()).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[tokio::test]
+    async fn ignores_an_expired_token_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, _) = counted_provider("a", 60);
+        first.with_cache_dir(dir.path().to_path_buf()).access_token().await.unwrap();
+
+        let (second, fetched) = counted_provider("a", 3600);
+        let second = second.with_cache_dir(dir.path().to_path_buf());
+        assert_eq!(second.access_token().await.unwrap(), "a-1");
+        assert_eq!(fetched.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn forgets_a_rejected_token_in_memory_and_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let (tokens, _) = counted_provider("a", 3600);
+        let tokens = tokens.with_cache_dir(dir.path().to_path_buf());
+        assert_eq!(tokens.access_token().await.unwrap(), "a-1");
+
+        assert!(tokens.report_unauthorized("a-1").await);
+        assert!(tokens.read_cache_file().is_none());
+        assert_eq!(tokens.access_token().await.unwrap(), "a-2");
+        assert_eq!(tokens.read_cache_file().unwrap().token, "a-2");
+    }
+
+    fn sharded(strategy: ShardingStrategy) -> ShardedTokenProvider {
+        ShardedTokenProvider::new(strategy)
+            .with_account("a", provider("a"))
+            .with_account("b", provider("b"))
+    }
+
+    #[tokio::test]
+    async fn round_robin_alternates_the_accounts() {
+        let tokens = sharded(ShardingStrategy::RoundRobin);
+        let mut used = Vec::new();
+        for _ in 0..4 {
+            used.push(tokens.access_token().await.unwrap());
+        }
+        assert_eq!(used, ["a-1", "b-1", "a-1", "b-1"]);
+        let requests: Vec<u64> = tokens.usage().iter().map(|usage| usage.requests).collect();
+        assert_eq!(requests, [2, 2]);
+    }
+
+    #[tokio::test]
+    async fn on_quota_moves_to_the_next_account_when_throttled() {
+        let tokens = sharded(ShardingStrategy::OnQuota);
+        assert_eq!(tokens.access_token().await.unwrap(), "a-1");
+        assert_eq!(tokens.access_token().await.unwrap(), "a-1");
+
+        assert!(tokens.report_quota_exceeded("a-1"));
+        assert_eq!(tokens.access_token().await.unwrap(), "b-1");
+        assert_eq!(tokens.usage()[0].throttled, 1);
+    }
+
+    #[tokio::test]
+    async fn skips_accounts_without_quota_left_this_minute() {
+        let tokens = sharded(ShardingStrategy::OnQuota).with_requests_per_minute(2);
+        let mut used = Vec::new();
+        for _ in 0..4 {
+            used.push(tokens.access_token().await.unwrap());
+        }
+        assert_eq!(used, ["a-1", "a-1", "b-1", "b-1"]);
+        // Both are out of quota, the requests go on rather than fail
+        assert!(tokens.access_token().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn unknown_or_single_tokens_are_not_retried() {
+        let tokens = sharded(ShardingStrategy::OnQuota);
+        tokens.access_token().await.unwrap();
+        assert!(!tokens.report_quota_exceeded("someone-else"));
+        assert_eq!(tokens.access_token().await.unwrap(), "a-1");
+
+        let single = ShardedTokenProvider::new(ShardingStrategy::OnQuota).with_account("a", provider("a"));
+        let token = single.access_token().await.unwrap();
+        assert!(!single.report_quota_exceeded(&token));
+    }
+}
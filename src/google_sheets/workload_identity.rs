@@ -0,0 +1,170 @@
+use crate::error::{Error, Result};
+use crate::google_sheets::token_provider::{AccessToken, TokenSource};
+use crate::http::client::ApiClient;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::env;
+
+// Lifetime requested for the impersonated token
+const TOKEN_LIFETIME: u64 = 3600;
+
+/// Keyless authentication of a GitHub Actions job through Workload Identity Federation.
+#[derive(Debug, Clone)]
+pub struct WorkloadIdentityConfig {
+    /// `projects/<number>/locations/global/workloadIdentityPools/<pool>/providers/<provider>`
+    pub provider: String,
+    /// Service account impersonated with the federated token.
+    pub service_account_email: String,
+    pub id_token_request_url: String,
+    pub id_token_request_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenResponse {
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct StsTokenResponse {
+    access_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateAccessTokenResponse {
+    access_token: String,
+}
+
+impl WorkloadIdentityConfig {
+    /// Takes the OIDC token request endpoint from the variables GitHub sets for the job.
+    pub fn from_env(provider: &str, service_account_email: &str) -> Result<Self> {
+        let missing = || {
+            Error::config(
+                "ACTIONS_ID_TOKEN_REQUEST_URL is not set, add `permissions: id-token: write` to the job",
+            )
+        };
+        let id_token_request_url = env::var("ACTIONS_ID_TOKEN_REQUEST_URL").map_err(|_| missing())?;
+        let id_token_request_token = env::var("ACTIONS_ID_TOKEN_REQUEST_TOKEN").map_err(|_| missing())?;
+
+        // Accept the provider with or without the IAM service prefix
+        let provider = provider
+            .trim()
+            .trim_start_matches("https:")
+            .trim_start_matches("//iam.googleapis.com/")
+            .to_string();
+        if !provider.starts_with("projects/") || !provider.contains("/providers/") {
+            return Err(Error::config(format!(
+                "workload identity provider '{}' must look like projects/<number>/locations/global/workloadIdentityPools/<pool>/providers/<provider>",
+                provider
+            )));
+        }
+
+        Ok(WorkloadIdentityConfig {
+            provider,
+            service_account_email: service_account_email.to_string(),
+            id_token_request_url,
+            id_token_request_token,
+        })
+    }
+}
+
+/// Exchanges the GitHub OIDC token for an access token of the service account.
+pub async fn get_workload_identity_token(
+    config: &WorkloadIdentityConfig,
+    scope: &str,
+) -> Result<String> {
+    Ok(request_workload_identity_token(&ApiClient::default(), config, scope).await?.token)
+}
+
+/// Token source impersonating the service account with the job's OIDC token.
+pub struct WorkloadIdentityTokenSource {
+    pub client: ApiClient,
+    pub config: WorkloadIdentityConfig,
+    pub scope: String,
+}
+
+impl WorkloadIdentityTokenSource {
+    pub fn new(client: ApiClient, config: WorkloadIdentityConfig, scope: &str) -> Self {
+        WorkloadIdentityTokenSource {
+            client,
+            config,
+            scope: scope.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl TokenSource for WorkloadIdentityTokenSource {
+    async fn fetch_token(&self) -> Result<AccessToken> {
+        request_workload_identity_token(&self.client, &self.config, &self.scope).await
+    }
+
+    fn cache_key(&self) -> String {
+        format!("wif-{}-{}", self.config.service_account_email, self.scope)
+    }
+}
+
+async fn request_workload_identity_token(
+    client: &ApiClient,
+    config: &WorkloadIdentityConfig,
+    scope: &str,
+) -> Result<AccessToken> {
+    // OIDC token of the job, issued for the workload identity provider
+    let resp = client
+        .http
+        .get(&config.id_token_request_url)
+        .query(&[("audience", format!("https://iam.googleapis.com/{}", config.provider))])
+        .bearer_auth(&config.id_token_request_token)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        return Err(Error::from_response(resp).await.context("Can't get GitHub OIDC token"));
+    }
+    let id_token: IdTokenResponse = resp.json().await?;
+
+    // Federated token from the Security Token Service
+    let body = serde_json::json!({
+        "grantType": "urn:ietf:params:oauth:grant-type:token-exchange",
+        "audience": format!("//iam.googleapis.com/{}", config.provider),
+        "scope": "https://www.googleapis.com/auth/cloud-platform",
+        "requestedTokenType": "urn:ietf:params:oauth:token-type:access_token",
+        "subjectTokenType": "urn:ietf:params:oauth:token-type:jwt",
+        "subjectToken": id_token.value,
+    });
+    let resp = client
+        .http
+        .post(&client.endpoints.sts_url)
+        .json(&body)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        return Err(Error::from_response(resp).await.context("Can't exchange the OIDC token"));
+    }
+    let federated: StsTokenResponse = resp.json().await?;
+
+    // Access token of the impersonated service account
+    let url = format!(
+        "{}/v1/projects/-/serviceAccounts/{}:generateAccessToken",
+        client.endpoints.iam_credentials_url,
+        config.service_account_email
+    );
+    let body = serde_json::json!({
+        "scope": scope.split_whitespace().collect::<Vec<_>>(),
+        "lifetime": format!("{}s", TOKEN_LIFETIME),
+    });
+    let resp = client
+        .http
+        .post(&url)
+        .bearer_auth(&federated.access_token)
+        .json(&body)
+        .send()
+        .await?;
+    if resp.status().is_success() {
+        let token: GenerateAccessTokenResponse = resp.json().await?;
+        AccessToken::new(token.access_token, TOKEN_LIFETIME)
+    } else {
+        Err(Error::from_response(resp)
+            .await
+            .context(format!("Can't impersonate {}", config.service_account_email)))
+    }
+}
@@ -0,0 +1,303 @@
+use crate::config::inputs::optional_input;
+use crate::error::{Error, Result};
+use reqwest::{Certificate, Client, Proxy};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const DEFAULT_SHEETS_BASE_URL: &str = "https://sheets.googleapis.com/v4";
+pub const DEFAULT_CLASSROOM_BASE_URL: &str = "https://classroom.googleapis.com/v1";
+pub const DEFAULT_GRAPH_BASE_URL: &str = "https://graph.microsoft.com/v1.0";
+pub const DEFAULT_MICROSOFT_LOGIN_URL: &str = "https://login.microsoftonline.com";
+pub const DEFAULT_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+pub const DEFAULT_AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+pub const DEFAULT_STS_URL: &str = "https://sts.googleapis.com/v1/token";
+pub const DEFAULT_IAM_CREDENTIALS_URL: &str = "https://iamcredentials.googleapis.com";
+
+/// Transport settings shared by every HTTP client of the crate.
+#[derive(Debug, Clone, Default)]
+pub struct HttpConfig {
+    /// Proxy for HTTP and HTTPS, e.g. `http://proxy.campus:3128`.
+    /// Without it the `HTTP_PROXY`/`HTTPS_PROXY` variables are respected.
+    pub proxy: Option<String>,
+    /// PEM files with additional root certificates, e.g. of an intercepting proxy.
+    pub ca_certificates: Vec<PathBuf>,
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+}
+
+/// Base URLs of the Google and Microsoft APIs, can point to a local emulator.
+#[derive(Debug, Clone)]
+pub struct Endpoints {
+    pub sheets_base_url: String,
+    pub classroom_base_url: String,
+    pub graph_base_url: String,
+    pub microsoft_login_url: String,
+    pub token_url: String,
+    pub auth_url: String,
+    pub sts_url: String,
+    pub iam_credentials_url: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Endpoints {
+            sheets_base_url: DEFAULT_SHEETS_BASE_URL.to_string(),
+            classroom_base_url: DEFAULT_CLASSROOM_BASE_URL.to_string(),
+            graph_base_url: DEFAULT_GRAPH_BASE_URL.to_string(),
+            microsoft_login_url: DEFAULT_MICROSOFT_LOGIN_URL.to_string(),
+            token_url: DEFAULT_TOKEN_URL.to_string(),
+            auth_url: DEFAULT_AUTH_URL.to_string(),
+            sts_url: DEFAULT_STS_URL.to_string(),
+            iam_credentials_url: DEFAULT_IAM_CREDENTIALS_URL.to_string(),
+        }
+    }
+}
+
+impl Endpoints {
+    /// Overrides the defaults with the `INPUT_*_URL` variables that are set.
+    pub fn from_inputs() -> Self {
+        let mut endpoints = Endpoints::default();
+        let overrides = [
+            ("INPUT_SHEETS_BASE_URL", &mut endpoints.sheets_base_url),
+            ("INPUT_CLASSROOM_BASE_URL", &mut endpoints.classroom_base_url),
+            ("INPUT_GRAPH_BASE_URL", &mut endpoints.graph_base_url),
+            ("INPUT_MICROSOFT_LOGIN_URL", &mut endpoints.microsoft_login_url),
+            ("INPUT_TOKEN_URL", &mut endpoints.token_url),
+            ("INPUT_AUTH_URL", &mut endpoints.auth_url),
+            ("INPUT_STS_URL", &mut endpoints.sts_url),
+            ("INPUT_IAM_CREDENTIALS_URL", &mut endpoints.iam_credentials_url),
+        ];
+        for (name, endpoint) in overrides {
+            if let Some(url) = optional_input(name) {
+                *endpoint = url.trim_end_matches('/').to_string();
+            }
+        }
+        endpoints
+    }
+}
+
+impl HttpConfig {
+    /// Reads `INPUT_PROXY`, `INPUT_CA_CERTIFICATES` (comma separated paths),
+    /// `INPUT_HTTP_TIMEOUT` and `INPUT_CONNECT_TIMEOUT` (seconds).
+    pub fn from_inputs() -> Result<Self> {
+        let seconds = |name: &str| -> Result<Option<Duration>> {
+            optional_input(name)
+                .map(|value| {
+                    value.trim().parse::<u64>().map(Duration::from_secs).map_err(|_| {
+                        Error::config(format!("{} must be a number of seconds, got '{}'", name, value))
+                    })
+                })
+                .transpose()
+        };
+
+        Ok(HttpConfig {
+            proxy: optional_input("INPUT_PROXY"),
+            ca_certificates: optional_input("INPUT_CA_CERTIFICATES")
+                .map(|paths| {
+                    paths
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|path| !path.is_empty())
+                        .map(PathBuf::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            timeout: seconds("INPUT_HTTP_TIMEOUT")?,
+            connect_timeout: seconds("INPUT_CONNECT_TIMEOUT")?,
+        })
+    }
+
+    pub fn build_client(&self) -> Result<Client> {
+        let mut builder = Client::builder();
+
+        if let Some(proxy) = &self.proxy {
+            let proxy = Proxy::all(proxy)
+                .map_err(|e| Error::config(format!("Invalid proxy '{}': {}", proxy, e)))?;
+            builder = builder.proxy(proxy);
+        }
+
+        for path in &self.ca_certificates {
+            let pem = fs::read(path).map_err(|e| {
+                Error::config(format!("Can't read CA certificate {}: {}", path.display(), e))
+            })?;
+            let certificates = Certificate::from_pem_bundle(&pem).map_err(|e| {
+                Error::config(format!("Invalid CA certificate {}: {}", path.display(), e))
+            })?;
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+
+        builder
+            .build()
+            .map_err(|e| Error::config(format!("Can't create the HTTP client: {}", e)))
+    }
+}
+
+/// HTTP client with the endpoints it talks to, cheap to clone.
+#[derive(Debug, Clone, Default)]
+pub struct ApiClient {
+    pub http: Client,
+    pub endpoints: Endpoints,
+}
+
+impl ApiClient {
+    pub fn new(config: &HttpConfig, endpoints: Endpoints) -> Result<Self> {
+        Ok(ApiClient {
+            http: config.build_client()?,
+            endpoints,
+        })
+    }
+
+    /// Reads the transport settings and the endpoints from the inputs.
+    pub fn from_inputs() -> Result<Self> {
+        ApiClient::new(&HttpConfig::from_inputs()?, Endpoints::from_inputs())
+    }
+
+    /// URL of a Sheets API resource, `path` starts with `/spreadsheets`.
+    pub fn sheets_url(&self, path: &str) -> String {
+        format!("{}{}", self.endpoints.sheets_base_url, path)
+    }
+
+    /// URL of a Classroom API resource, `path` starts with `/courses`.
+    pub fn classroom_url(&self, path: &str) -> String {
+        format!("{}{}", self.endpoints.classroom_base_url, path)
+    }
+
+    /// URL of a Microsoft Graph resource, `path` starts with `/`.
+    pub fn graph_url(&self, path: &str) -> String {
+        format!("{}{}", self.endpoints.graph_base_url, path)
+    }
+}
+
+/// Percent-encodes everything but the unreserved characters and `extra_safe`, for a URL path segment.
+pub fn percent_encode(text: &str, extra_safe: &[u8]) -> String {
+    text.bytes()
+        .map(|byte| {
+            if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) || extra_safe.contains(&byte) {
+                (byte as char).to_string()
+            } else {
+                format!("%{:02X}", byte)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::x509::{X509NameBuilder, X509};
+    use std::env;
+
+    // Self-signed certificate of a campus proxy, as PEM
+    fn ca_certificate() -> Vec<u8> {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", "Campus proxy CA").unwrap();
+        let name = name.build();
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        builder.build().to_pem().unwrap()
+    }
+
+    // The inputs are process-wide, so every case that sets them is in this test
+    #[test]
+    fn reads_the_transport_settings_and_the_endpoints() {
+        env::set_var("INPUT_PROXY", "http://proxy.campus:3128");
+        env::set_var("INPUT_CA_CERTIFICATES", " certs/campus.pem, ,certs/extra.pem ");
+        env::set_var("INPUT_HTTP_TIMEOUT", "30");
+        env::set_var("INPUT_CONNECT_TIMEOUT", " 5 ");
+        env::set_var("INPUT_GRAPH_BASE_URL", "http://localhost:8080/v1.0/");
+
+        let config = HttpConfig::from_inputs().unwrap();
+        assert_eq!(config.proxy.as_deref(), Some("http://proxy.campus:3128"));
+        assert_eq!(config.ca_certificates, [PathBuf::from("certs/campus.pem"), PathBuf::from("certs/extra.pem")]);
+        assert_eq!(config.timeout, Some(Duration::from_secs(30)));
+        assert_eq!(config.connect_timeout, Some(Duration::from_secs(5)));
+
+        let endpoints = Endpoints::from_inputs();
+        assert_eq!(endpoints.graph_base_url, "http://localhost:8080/v1.0");
+        assert_eq!(endpoints.sheets_base_url, DEFAULT_SHEETS_BASE_URL);
+
+        for timeout in ["30s", "-1", "1.5"] {
+            env::set_var("INPUT_HTTP_TIMEOUT", timeout);
+            let err = HttpConfig::from_inputs().unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::Config);
+            assert!(err.to_string().contains("INPUT_HTTP_TIMEOUT must be a number of seconds"), "{}", err);
+        }
+
+        for name in ["INPUT_PROXY", "INPUT_CA_CERTIFICATES", "INPUT_HTTP_TIMEOUT", "INPUT_CONNECT_TIMEOUT", "INPUT_GRAPH_BASE_URL"] {
+            env::remove_var(name);
+        }
+    }
+
+    #[test]
+    fn builds_a_client_with_a_proxy_and_ca_certificates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("campus.pem");
+        fs::write(&path, ca_certificate()).unwrap();
+
+        let config = HttpConfig {
+            proxy: Some("http://proxy.campus:3128".to_string()),
+            ca_certificates: vec![path],
+            timeout: Some(Duration::from_secs(30)),
+            connect_timeout: Some(Duration::from_secs(5)),
+        };
+        config.build_client().unwrap();
+    }
+
+    #[test]
+    fn rejects_an_invalid_proxy_or_certificate() {
+        let config = HttpConfig {
+            proxy: Some("http://[proxy".to_string()),
+            ..HttpConfig::default()
+        };
+        let err = config.build_client().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Config);
+        assert!(err.to_string().contains("Invalid proxy"), "{}", err);
+
+        let dir = tempfile::tempdir().unwrap();
+        let missing = HttpConfig {
+            ca_certificates: vec![dir.path().join("missing.pem")],
+            ..HttpConfig::default()
+        };
+        let err = missing.build_client().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Config);
+        assert!(err.to_string().contains("Can't read CA certificate"), "{}", err);
+
+        let path = dir.path().join("broken.pem");
+        fs::write(&path, "-----BEGIN CERTIFICATE-----\nbm90IGEgY2VydGlmaWNhdGU=\n-----END CERTIFICATE-----\n").unwrap();
+        let broken = HttpConfig {
+            ca_certificates: vec![path],
+            ..HttpConfig::default()
+        };
+        let err = broken.build_client().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Config);
+        assert!(err.to_string().contains("Invalid CA certificate"), "{}", err);
+    }
+
+    #[test]
+    fn percent_encodes_path_segments() {
+        assert_eq!(percent_encode("Sheet 1!A1:B2", b"!:"), "Sheet%201!A1:B2");
+        assert_eq!(percent_encode("hw/ü", b""), "hw%2F%C3%BC");
+    }
+}
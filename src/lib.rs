@@ -0,0 +1,72 @@
+//! Sends GitHub Classroom autograding results to a Google Sheets gradebook.
+//!
+//! The gradebook is a sheet with a `github_id` column and one column per assignment.
+//! [`StudentManager`] finds (or creates) the row of a student and the column of an
+//! assignment and writes the result into their intersection. It is one implementation
+//! of [`GradeSink`], the storage the results are recorded in.
+//!
+//! API calls get their access token from a [`TokenProvider`]. [`CachedTokenProvider`]
+//! refreshes the token of a [`TokenSource`] (service account key, workload identity,
+//! a signed-in user) before it expires; a plain `String` token can be used as well.
+//!
+//! ```no_run
+//! use github_classroom_spreadsheets_integration::{
+//!     get_access_token, parse_results, ApiClient, GradeSink, Result, StatusValues,
+//!     StudentManager,
+//! };
+//!
+//! # async fn run(email: &str, key: &str, encoded: &str) -> Result<()> {
+//! let token = get_access_token(email, key, "https://www.googleapis.com/auth/spreadsheets").await?;
+//! let client = ApiClient::default();
+//! let manager = StudentManager::new(&client, &token, "spreadsheet-id", "Sheet1");
+//!
+//! let results = parse_results(encoded)?;
+//! manager.record_results("octocat", &results, &StatusValues::default()).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Every operation returns [`Error`], its [`ErrorKind`] tells configuration, authentication,
+//! permission, quota, not-found, parse and network problems apart.
+
+pub mod config;
+pub mod data_processing;
+pub mod error;
+pub mod google_sheets;
+pub mod http;
+pub mod microsoft_graph;
+pub mod sinks;
+pub mod students;
+pub mod utils;
+
+pub use error::{Error, ErrorKind, Result};
+pub use google_sheets::auth::{
+    get_access_token, get_service_account_token, ServiceAccountKey, ServiceAccountTokenSource,
+};
+pub use google_sheets::oauth::{OAuthClientConfig, OAuthTokenSource};
+pub use google_sheets::token_provider::{
+    AccessToken, CachedTokenProvider, ShardUsage, ShardedTokenProvider, ShardingStrategy,
+    TokenProvider, TokenSource,
+};
+pub use google_sheets::workload_identity::{
+    get_workload_identity_token, WorkloadIdentityConfig, WorkloadIdentityTokenSource,
+};
+pub use http::client::{ApiClient, Endpoints, HttpConfig};
+pub use microsoft_graph::auth::{ClientCredentials, ClientCredentialsTokenSource};
+pub use sinks::canvas_sink::{CanvasConfig, CanvasGradeSink};
+pub use sinks::classroom_sink::{ClassroomConfig, ClassroomGrade, ClassroomGradeSink};
+pub use sinks::csv_sink::CsvGradeSink;
+pub use sinks::excel_online_sink::ExcelOnlineGradeSink;
+pub use sinks::git_sink::{GitConfig, GitGradeSink};
+pub use sinks::grade_sink::GradeSink;
+pub use sinks::json_sink::JsonGradeSink;
+pub use sinks::moodle_sink::{MoodleConfig, MoodleGradeFunction, MoodleGradeSink};
+#[cfg(feature = "postgres")]
+pub use sinks::postgres_store::PostgresGradeStore;
+pub use sinks::sqlite_store::SqliteGradeStore;
+pub use sinks::webhook_sink::{SubmissionEvent, WebhookConfig, WebhookSink};
+pub use sinks::xlsx_sink::XlsxGradeSink;
+pub use students::student_manager::StudentManager;
+pub use utils::autograding_config::{apply_weights, load_autograding_config, AutogradingConfig};
+pub use utils::json_parser::{merge_results, parse_results, TestResult, TestResults, TestStatus};
+pub use utils::status_values::StatusValues;
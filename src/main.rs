@@ -1,56 +1,346 @@
-mod google_sheets;
-mod data_processing;
-mod students;
-mod utils;
-
-use google_sheets::auth::get_access_token;
-use students::student_manager::StudentManager;
-use reqwest::Client;
-use tokio;
-use std::env;
-use utils::json_parser::{parse_results};
+use github_classroom_spreadsheets_integration::config::auth::token_provider_from_inputs;
+use github_classroom_spreadsheets_integration::config::inputs::{
+    optional_input, required_input, set_profile, workspace_path,
+};
+use github_classroom_spreadsheets_integration::config::routing::route_from_inputs;
+use github_classroom_spreadsheets_integration::config::targets::{
+    sheet_name, targets_from_inputs, SheetTarget,
+};
+use github_classroom_spreadsheets_integration::{
+    apply_weights, load_autograding_config, merge_results, parse_results, ApiClient,
+    CachedTokenProvider, CanvasConfig, CanvasGradeSink, ClassroomConfig, ClassroomGradeSink,
+    ClientCredentials, ClientCredentialsTokenSource, CsvGradeSink, Error, ExcelOnlineGradeSink,
+    GitConfig, GitGradeSink, GradeSink, MoodleConfig, MoodleGradeSink, Result, ShardedTokenProvider,
+    SqliteGradeStore, StatusValues, StudentManager, TestResults, WebhookConfig, WebhookSink,
+    XlsxGradeSink,
+};
+use github_classroom_spreadsheets_integration::microsoft_graph::auth::GRAPH_SCOPE;
+use github_classroom_spreadsheets_integration::sinks::classroom_sink::CLASSROOM_SCOPE;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process;
+
+// Reads the results of every runner listed in `runners` (comma separated ids).
+// The autograding-grading-reporter exposes them as `<RUNNER_ID>_RESULTS` variables.
+fn read_runner_results(runners: &str) -> Result<TestResults> {
+    let mut results = Vec::new();
+    for runner_id in runners.split(',').map(str::trim).filter(|id| !id.is_empty()) {
+        let var_name = format!("{}_RESULTS", runner_id.to_uppercase());
+        let encoded = optional_input(&var_name).ok_or_else(|| {
+            Error::config(format!("Results of runner '{}' not found in {}", runner_id, var_name))
+        })?;
+        results.push(parse_results(&encoded)?);
+    }
+    merge_results(results)
+}
+
+async fn run() -> Result<()> {
+    // Gradebook and inputs of the course of the repository, when the action serves several courses
+    if let Some((routing, route)) = route_from_inputs()? {
+        println!("Using the route {}", route.describe());
+        let inputs = routing.inputs(&route);
+        let mut overridden: Vec<&str> = inputs
+            .keys()
+            .filter(|name| optional_input(name).is_some())
+            .map(String::as_str)
+            .collect();
+        if !overridden.is_empty() {
+            overridden.sort_unstable();
+            println!("The route overrides the inputs {} of the step", overridden.join(", "));
+        }
+        set_profile(inputs)?;
+    }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Read environment variables
-    let input_results_base64 = env::var("INPUT_RESULTS")?;
-    let student_github_id = env::var("INPUT_STUDENT_NAME")?;
-    let robot_email = env::var("INPUT_ROBOT_EMAIL")?;
-    let private_api_key_raw = env::var("INPUT_PRIVATE_API_KEY")?;
-    let table_id = env::var("INPUT_TABLE_ID")?;
+    let student_github_id = required_input("INPUT_STUDENT_NAME")?;
+
+    // Decode and parse the test results, either of several runners or of a single one
+    let mut test_results = match optional_input("INPUT_RUNNERS") {
+        Some(runners) => read_runner_results(&runners)?,
+        None => parse_results(&required_input("INPUT_RESULTS")?)?,
+    };
+
+    // Attach the points from autograding.json (path is relative to the checkout)
+    let autograding_config_path = optional_input("INPUT_AUTOGRADING_CONFIG");
+    let use_scores = autograding_config_path.is_some();
+    if let Some(autograding_config_path) = autograding_config_path {
+        let config = load_autograding_config(&workspace_path(&autograding_config_path))?;
+        apply_weights(&mut test_results, &config);
+    }
+
+    // Values written for each test status
+    let status_values = StatusValues::from_json(
+        &optional_input("INPUT_STATUS_VALUES").unwrap_or_default(),
+        use_scores,
+    )?;
+
+    // Storage of the gradebook
+    let backend = optional_input("INPUT_BACKEND").unwrap_or_else(|| "sheets".to_string());
+    let recorded = match backend.trim() {
+        "sheets" => record_to_sheets(&student_github_id, &test_results, &status_values).await,
+        "csv" => {
+            let sink = CsvGradeSink::new(workspace_path(&required_input("INPUT_CSV_PATH")?));
+            sink.record_results(&student_github_id, &test_results, &status_values).await
+        }
+        "xlsx" => {
+            let sheet_name = optional_input("INPUT_XLSX_SHEET").unwrap_or_else(|| "Sheet1".to_string());
+            let sink = XlsxGradeSink::new(workspace_path(&required_input("INPUT_XLSX_PATH")?), &sheet_name);
+            sink.record_results(&student_github_id, &test_results, &status_values).await
+        }
+        "sqlite" => record_to_sqlite(&student_github_id, &test_results, &status_values).await,
+        #[cfg(feature = "postgres")]
+        "postgres" => record_to_postgres(&student_github_id, &test_results, &status_values).await,
+        #[cfg(not(feature = "postgres"))]
+        "postgres" => Err(Error::config(
+            "The postgres backend is not available, build with `--features postgres`",
+        )),
+        "canvas" => {
+            let sink = CanvasGradeSink::new(ApiClient::from_inputs()?, CanvasConfig::from_inputs()?);
+            sink.record_results(&student_github_id, &test_results, &status_values).await
+        }
+        "moodle" => {
+            let sink = MoodleGradeSink::new(ApiClient::from_inputs()?, MoodleConfig::from_inputs()?);
+            sink.record_results(&student_github_id, &test_results, &status_values).await
+        }
+        "classroom" => record_to_classroom(&student_github_id, &test_results, &status_values).await,
+        "excel-online" => record_to_excel_online(&student_github_id, &test_results, &status_values).await,
+        "git" => {
+            let sink = GitGradeSink::new(GitConfig::from_inputs()?);
+            sink.record_results(&student_github_id, &test_results, &status_values).await
+        }
+        other => Err(Error::config(format!(
+            "Unknown backend '{}', expected 'sheets', 'csv', 'xlsx', 'sqlite', 'postgres', 'canvas', 'moodle', 'classroom', 'excel-online' or 'git'",
+            other
+        ))),
+    };
+    recorded?;
+
+    // Notify the webhook once the grades are stored
+    if optional_input("INPUT_WEBHOOK_URL").is_some() {
+        let webhook = WebhookSink::new(ApiClient::from_inputs()?, WebhookConfig::from_inputs()?);
+        webhook.record_results(&student_github_id, &test_results, &status_values).await?;
+    }
+    Ok(())
+}
+
+// Client, tokens and spreadsheet of the Google Sheets gradebook
+async fn sheets_access() -> Result<(ApiClient, ShardedTokenProvider, String)> {
+    let table_id = required_input("INPUT_TABLE_ID")?;
 
-    // Decode and parse the private key
-    let private_api_key = private_api_key_raw.replace("\\n", "\n");
+    // Client shared by all requests (proxy, certificates, endpoints)
+    let client = ApiClient::from_inputs()?;
 
-    // Decode and parse the test results
-    let test_results = parse_results(&input_results_base64)?;
+    let delegated_user = optional_input("INPUT_DELEGATED_USER");
+    let token_provider = sheets_tokens(&client, delegated_user.as_deref()).await?;
+
+    Ok((client, token_provider, table_id))
+}
 
-    // Get the access token
+// Tokens of the configured accounts, shared by every request
+async fn sheets_tokens(client: &ApiClient, delegated_user: Option<&str>) -> Result<ShardedTokenProvider> {
     let scope = "https://www.googleapis.com/auth/spreadsheets";
-    let access_token = get_access_token(&robot_email, &private_api_key, scope).await?;
+    token_provider_from_inputs(client, scope, delegated_user).await
+}
+
+// Quota usage of each account when the requests are shared
+fn print_usage(token_provider: &ShardedTokenProvider) {
+    let usage = token_provider.usage();
+    if usage.len() > 1 {
+        for shard in usage {
+            println!(
+                "{}: {} requests, {} rate limited",
+                shard.account, shard.requests, shard.throttled
+            );
+        }
+    }
+}
+
+// Writes the results into every Google Sheets gradebook, a failing target doesn't stop the others
+async fn record_to_sheets(
+    student_github_id: &str,
+    test_results: &TestResults,
+    status_values: &StatusValues,
+) -> Result<()> {
+    let targets = targets_from_inputs()?;
+    let client = ApiClient::from_inputs()?;
+
+    // Tokens of each delegated user, shared by its targets
+    let mut token_providers = HashMap::new();
+    let mut outcomes = Vec::new();
+    for target in &targets {
+        let outcome = record_to_target(
+            &client,
+            &mut token_providers,
+            target,
+            student_github_id,
+            test_results,
+            status_values,
+        )
+        .await;
+        outcomes.push(outcome);
+    }
+
+    for token_provider in token_providers.values() {
+        print_usage(token_provider);
+    }
+
+    // A single gradebook fails with its own error
+    if outcomes.len() == 1 {
+        return outcomes.remove(0);
+    }
 
-    // Sheet name
-    let sheet_name = "Sheet1";
+    println!("Targets:");
+    for (target, outcome) in targets.iter().zip(&outcomes) {
+        match outcome {
+            Ok(()) => println!("  {} / {}: recorded", target.spreadsheet_id, target.sheet),
+            Err(err) => println!("  {} / {}: failed: {}", target.spreadsheet_id, target.sheet, err),
+        }
+    }
+    let failures: Vec<&Error> = outcomes.iter().filter_map(|outcome| outcome.as_ref().err()).collect();
+    match failures.first() {
+        None => Ok(()),
+        Some(first) => Err(Error::new(
+            first.kind(),
+            format!("{} of {} targets failed, see the summary above", failures.len(), targets.len()),
+        )),
+    }
+}
 
-    // Client
-    let client = Client::new();
+// Writes the results into the sheet of one target
+async fn record_to_target(
+    client: &ApiClient,
+    token_providers: &mut HashMap<Option<String>, ShardedTokenProvider>,
+    target: &SheetTarget,
+    student_github_id: &str,
+    test_results: &TestResults,
+    status_values: &StatusValues,
+) -> Result<()> {
+    let token_provider = match token_providers.entry(target.delegated_user.clone()) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => entry.insert(sheets_tokens(client, target.delegated_user.as_deref()).await?),
+    };
 
     let student_manager = StudentManager::new(
-        &client,
-        &access_token,
-        &table_id,
-        sheet_name,
+        client,
+        token_provider,
+        &target.spreadsheet_id,
+        &target.sheet,
     );
 
     // Check the test results and update the student's grades
-    for test in test_results.tests {
-        let assignment_name = test.name;
-        let result = if test.status == "pass" { 1 } else { 0 };
+    student_manager
+        .record_results(student_github_id, test_results, status_values)
+        .await
+}
+
+// Stores the results in the database and mirrors it to the sheet, if one is configured
+async fn record_to_sqlite(
+    student_github_id: &str,
+    test_results: &TestResults,
+    status_values: &StatusValues,
+) -> Result<()> {
+    let store = SqliteGradeStore::open(&workspace_path(&required_input("INPUT_SQLITE_PATH")?))?;
+    store
+        .record_results(student_github_id, test_results, status_values)
+        .await?;
+
+    if optional_input("INPUT_TABLE_ID").is_none() {
+        return Ok(());
+    }
+    let (client, token_provider, table_id) = sheets_access().await?;
+    let sheet_name = sheet_name();
+    let student_manager = StudentManager::new(&client, &token_provider, &table_id, &sheet_name);
+    let written = store.sync_to_sheet(&student_manager).await?;
+    println!("Synced the database to the sheet, {} cells updated", written);
+
+    print_usage(&token_provider);
+    Ok(())
+}
 
-        student_manager
-            .update_assignment_result(&student_github_id, &assignment_name, result)
-            .await?;
+// Stores the results in PostgreSQL and mirrors it to the sheet, if one is configured
+#[cfg(feature = "postgres")]
+async fn record_to_postgres(
+    student_github_id: &str,
+    test_results: &TestResults,
+    status_values: &StatusValues,
+) -> Result<()> {
+    use github_classroom_spreadsheets_integration::PostgresGradeStore;
+
+    let pool_size = match optional_input("INPUT_DATABASE_POOL_SIZE") {
+        Some(size) => size.trim().parse().map_err(|_| {
+            Error::config(format!("INPUT_DATABASE_POOL_SIZE must be a number, got '{}'", size))
+        })?,
+        None => 4,
+    };
+    let store = PostgresGradeStore::connect(&required_input("INPUT_DATABASE_URL")?, pool_size).await?;
+    store
+        .record_results(student_github_id, test_results, status_values)
+        .await?;
+
+    if optional_input("INPUT_TABLE_ID").is_none() {
+        return Ok(());
     }
+    let (client, token_provider, table_id) = sheets_access().await?;
+    let sheet_name = sheet_name();
+    let student_manager = StudentManager::new(&client, &token_provider, &table_id, &sheet_name);
+    let written = store.sync_to_sheet(&student_manager).await?;
+    println!("Synced the database to the sheet, {} cells updated", written);
+
+    print_usage(&token_provider);
+    Ok(())
+}
+
+// Sets the grades of the Google Classroom coursework
+async fn record_to_classroom(
+    student_github_id: &str,
+    test_results: &TestResults,
+    status_values: &StatusValues,
+) -> Result<()> {
+    let client = ApiClient::from_inputs()?;
+
+    // Classroom needs its own scope, service accounts act on behalf of a teacher
+    let delegated_user = optional_input("INPUT_DELEGATED_USER");
+    let token_provider =
+        token_provider_from_inputs(&client, CLASSROOM_SCOPE, delegated_user.as_deref()).await?;
 
+    let sink = ClassroomGradeSink::new(&client, &token_provider, ClassroomConfig::from_inputs()?);
+    sink.record_results(student_github_id, test_results, status_values)
+        .await?;
+
+    print_usage(&token_provider);
     Ok(())
 }
+
+// Writes the results into a worksheet of an Excel workbook through Microsoft Graph
+async fn record_to_excel_online(
+    student_github_id: &str,
+    test_results: &TestResults,
+    status_values: &StatusValues,
+) -> Result<()> {
+    let client = ApiClient::from_inputs()?;
+    let workbook = required_input("INPUT_EXCEL_WORKBOOK")?;
+    let sheet_name = optional_input("INPUT_EXCEL_SHEET").unwrap_or_else(|| "Sheet1".to_string());
+
+    // Token of the app registration, cached like the Google tokens
+    let source = ClientCredentialsTokenSource::new(client.clone(), ClientCredentials::from_inputs()?, GRAPH_SCOPE);
+    let mut token_provider = CachedTokenProvider::new(Box::new(source));
+    if let Some(cache_dir) = optional_input("INPUT_TOKEN_CACHE_DIR") {
+        token_provider = token_provider.with_cache_dir(PathBuf::from(cache_dir));
+    }
+
+    let sink = ExcelOnlineGradeSink::new(&client, &token_provider, workbook.trim(), &sheet_name);
+    sink.record_results(student_github_id, test_results, status_values)
+        .await
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(err) = run().await {
+        eprintln!("Error: {}", err);
+        if let Some(hint) = err.hint() {
+            eprintln!("Hint: {}", hint);
+        }
+        process::exit(err.exit_code());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::data_processing::table::cell_text;
+use crate::data_processing::utils::{cell_address_to_coords, coords_to_cell_address};
+use crate::error::{Error, Result};
+use crate::google_sheets::api::send;
+use crate::google_sheets::token_provider::TokenProvider;
+use crate::http::client::{percent_encode, ApiClient};
+use serde_json::Value;
+
+// Graph executes up to 20 requests of a JSON batch
+const BATCH_SIZE: usize = 20;
+
+fn worksheet_path(workbook: &str, sheet_name: &str) -> String {
+    // Worksheet names may contain spaces and other characters that are not allowed in a URL path
+    format!("{}/workbook/worksheets/{}", workbook, percent_encode(sheet_name, b""))
+}
+
+/// Reads the used range of the worksheet as a table starting at A1.
+///
+/// `workbook` is the Graph path of the file, e.g. `/drives/{drive-id}/items/{item-id}`.
+pub async fn read_used_range(
+    client: &ApiClient,
+    tokens: &dyn TokenProvider,
+    workbook: &str,
+    sheet_name: &str,
+) -> Result<Vec<Vec<String>>> {
+    // URL
+    let url = client.graph_url(&format!(
+        "{}/usedRange(valuesOnly=true)",
+        worksheet_path(workbook, sheet_name)
+    ));
+
+    // Sending request
+    let resp = send(tokens, client.http.get(&url)).await?;
+
+    if !resp.status().is_success() {
+        return Err(Error::from_response(resp).await.context(format!("Can't load the worksheet {}", sheet_name)));
+    }
+    let data: Value = resp.json().await?;
+
+    // The used range starts at its first non-empty cell, e.g. `Sheet1!B2:D9`
+    let address = data["address"].as_str().unwrap_or("A1");
+    let (top, left) = cell_address_to_coords(address)
+        .ok_or_else(|| Error::parse(format!("Invalid range address '{}'", address)))?;
+
+    let mut table = vec![Vec::new(); top];
+    for row in data["values"].as_array().into_iter().flatten() {
+        let mut cells = vec![String::new(); left];
+        cells.extend(row.as_array().into_iter().flatten().map(cell_text));
+        table.push(cells);
+    }
+    Ok(table)
+}
+
+/// Writes the cells with a JSON batch of `PATCH range` requests, executed one after another.
+pub async fn write_cells(
+    client: &ApiClient,
+    tokens: &dyn TokenProvider,
+    workbook: &str,
+    sheet_name: &str,
+    cells: &[(usize, usize, Value)],
+) -> Result<()> {
+    // URL
+    let url = client.graph_url("/$batch");
+    let worksheet = worksheet_path(workbook, sheet_name);
+
+    for chunk in cells.chunks(BATCH_SIZE) {
+        // Request body, each request waits for the previous one: Excel edits a workbook one at a time
+        let requests: Vec<Value> = chunk
+            .iter()
+            .enumerate()
+            .map(|(index, (row, col, value))| {
+                let mut request = serde_json::json!({
+                    "id": (index + 1).to_string(),
+                    "method": "PATCH",
+                    "url": format!("{}/range(address='{}')", worksheet, coords_to_cell_address(*row, *col)),
+                    "headers": {"Content-Type": "application/json"},
+                    "body": {"values": [[value]]}
+                });
+                if index > 0 {
+                    request["dependsOn"] = serde_json::json!([index.to_string()]);
+                }
+                request
+            })
+            .collect();
+        let body = serde_json::json!({ "requests": requests });
+
+        // Sending request
+        let resp = send(tokens, client.http.post(&url).json(&body)).await?;
+
+        if !resp.status().is_success() {
+            return Err(Error::from_response(resp).await.context("Can't write the cells"));
+        }
+        let data: Value = resp.json().await?;
+
+        // 424 means that an earlier request of the batch failed, report that one
+        let failed = data["responses"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|response| !(200..300).contains(&response["status"].as_u64().unwrap_or(0)))
+            .min_by_key(|response| response["status"].as_u64() == Some(424));
+        if let Some(response) = failed {
+            let address = response["id"]
+                .as_str()
+                .and_then(|id| id.parse::<usize>().ok())
+                .and_then(|id| chunk.get(id.checked_sub(1)?))
+                .map(|(row, col, _)| coords_to_cell_address(*row, *col))
+                .unwrap_or_default();
+            let status = response["status"].as_u64().unwrap_or(500) as u16;
+            return Err(Error::from_status(status, &response["body"].to_string())
+                .context(format!("Can't write data {}", address)));
+        }
+    }
+    Ok(())
+}
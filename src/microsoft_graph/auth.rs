@@ -0,0 +1,80 @@
+use crate::config::inputs::required_input;
+use crate::error::{Error, Result};
+use crate::google_sheets::auth::TokenResponse;
+use crate::google_sheets::token_provider::{AccessToken, TokenSource};
+use crate::http::client::ApiClient;
+use async_trait::async_trait;
+
+/// Scope of an app registration calling Microsoft Graph with its application permissions.
+pub const GRAPH_SCOPE: &str = "https://graph.microsoft.com/.default";
+
+/// App registration in Microsoft Entra ID (Azure AD) with a client secret.
+#[derive(Debug, Clone)]
+pub struct ClientCredentials {
+    pub tenant_id: String,
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+impl ClientCredentials {
+    /// Reads `INPUT_MICROSOFT_TENANT_ID`, `INPUT_MICROSOFT_CLIENT_ID` and `INPUT_MICROSOFT_CLIENT_SECRET`.
+    pub fn from_inputs() -> Result<Self> {
+        Ok(ClientCredentials {
+            tenant_id: required_input("INPUT_MICROSOFT_TENANT_ID")?.trim().to_string(),
+            client_id: required_input("INPUT_MICROSOFT_CLIENT_ID")?.trim().to_string(),
+            client_secret: required_input("INPUT_MICROSOFT_CLIENT_SECRET")?.trim().to_string(),
+        })
+    }
+}
+
+/// Token source of the OAuth client credentials flow of the Microsoft identity platform.
+pub struct ClientCredentialsTokenSource {
+    pub client: ApiClient,
+    pub credentials: ClientCredentials,
+    pub scope: String,
+}
+
+impl ClientCredentialsTokenSource {
+    pub fn new(client: ApiClient, credentials: ClientCredentials, scope: &str) -> Self {
+        ClientCredentialsTokenSource {
+            client,
+            credentials,
+            scope: scope.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl TokenSource for ClientCredentialsTokenSource {
+    async fn fetch_token(&self) -> Result<AccessToken> {
+        // URL
+        let url = format!(
+            "{}/{}/oauth2/v2.0/token",
+            self.client.endpoints.microsoft_login_url, self.credentials.tenant_id
+        );
+
+        // Request body
+        let params = [
+            ("grant_type", "client_credentials"),
+            ("client_id", self.credentials.client_id.as_str()),
+            ("client_secret", self.credentials.client_secret.as_str()),
+            ("scope", self.scope.as_str()),
+        ];
+
+        // Sending request
+        let resp = self.client.http.post(&url).form(&params).send().await?;
+
+        if resp.status().is_success() {
+            let token_response: TokenResponse = resp.json().await?;
+            AccessToken::new(token_response.access_token, token_response.expires_in as u64)
+        } else {
+            Err(Error::from_response(resp)
+                .await
+                .context("Can't get a Microsoft access token"))
+        }
+    }
+
+    fn cache_key(&self) -> String {
+        format!("{}-{}-{}", self.credentials.tenant_id, self.credentials.client_id, self.scope)
+    }
+}
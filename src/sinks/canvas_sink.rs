@@ -0,0 +1,145 @@
+use crate::config::inputs::{required_input, workspace_path};
+use crate::data_processing::roster::{load_mapping, parse_mapping};
+use crate::data_processing::table::cell_text;
+use crate::error::{Error, Result};
+use crate::http::client::ApiClient;
+use crate::sinks::grade_sink::GradeSink;
+use crate::sinks::id_mapping::IdMapping;
+use crate::utils::json_parser::TestResults;
+use crate::utils::status_values::StatusValues;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Canvas course the grades are posted to.
+#[derive(Debug, Clone)]
+pub struct CanvasConfig {
+    /// Address of the Canvas instance, e.g. `https://canvas.university.edu`.
+    pub base_url: String,
+    pub course_id: String,
+    /// Access token of a teacher of the course.
+    pub token: String,
+    /// github_id -> Canvas user id (or `sis_user_id:...`, `sis_login_id:...`).
+    pub roster: HashMap<String, String>,
+    /// Assignment (test) name -> Canvas assignment id.
+    pub assignments: HashMap<String, String>,
+}
+
+impl CanvasConfig {
+    /// Reads the `INPUT_CANVAS_*` inputs. The roster is a CSV file with `github_id`
+    /// (or `github_username`) and `canvas_user_id` columns.
+    pub fn from_inputs() -> Result<Self> {
+        let roster_path = workspace_path(&required_input("INPUT_CANVAS_ROSTER")?);
+        Ok(CanvasConfig {
+            base_url: required_input("INPUT_CANVAS_BASE_URL")?.trim().trim_end_matches('/').to_string(),
+            course_id: required_input("INPUT_CANVAS_COURSE_ID")?.trim().to_string(),
+            token: required_input("INPUT_CANVAS_TOKEN")?.trim().to_string(),
+            roster: load_mapping(&roster_path, &["github_id", "github_username"], "canvas_user_id")?,
+            assignments: parse_mapping(
+                &required_input("INPUT_CANVAS_ASSIGNMENTS")?,
+                "INPUT_CANVAS_ASSIGNMENTS",
+            )?,
+        })
+    }
+}
+
+/// Posts grades to a Canvas course through the Submissions API.
+///
+/// Canvas has no rows and columns to create: students must be in the roster and assignments
+/// in the assignment map, see [`IdMapping`].
+pub struct CanvasGradeSink {
+    client: ApiClient,
+    config: CanvasConfig,
+    mapping: IdMapping,
+}
+
+impl CanvasGradeSink {
+    pub fn new(client: ApiClient, config: CanvasConfig) -> Self {
+        let mapping = IdMapping::new("Canvas", &config.roster, &config.assignments);
+        CanvasGradeSink {
+            client,
+            config,
+            mapping,
+        }
+    }
+
+    fn submission_url(&self, student: usize, assignment: usize) -> Result<String> {
+        let (user_id, assignment_id) = self.mapping.ids(student, assignment)?;
+        Ok(format!(
+            "{}/api/v1/courses/{}/assignments/{}/submissions/{}",
+            self.config.base_url, self.config.course_id, assignment_id, user_id
+        ))
+    }
+}
+
+#[async_trait]
+impl GradeSink for CanvasGradeSink {
+    async fn find_or_create_student(&self, github_id: &str) -> Result<usize> {
+        self.mapping.student(github_id)
+    }
+
+    async fn find_or_create_assignment(&self, assignment_name: &str) -> Result<usize> {
+        self.mapping.assignment(assignment_name)
+    }
+
+    async fn read_cell(&self, student: usize, assignment: usize) -> Result<Option<String>> {
+        // URL
+        let url = self.submission_url(student, assignment)?;
+
+        // Sending request
+        let resp = self
+            .client
+            .http
+            .get(&url)
+            .bearer_auth(&self.config.token)
+            .send()
+            .await?;
+
+        if resp.status().is_success() {
+            let submission: Value = resp.json().await?;
+            Ok(submission["grade"].as_str().map(str::to_string))
+        } else {
+            Err(Error::from_response(resp).await.context("Can't load the Canvas submission"))
+        }
+    }
+
+    async fn write_cell(&self, student: usize, assignment: usize, value: Value) -> Result<()> {
+        // URL
+        let url = self.submission_url(student, assignment)?;
+
+        // Request body, Canvas converts the grade to the grading type of the assignment
+        let grade = cell_text(&value);
+        let params = [("submission[posted_grade]", grade.as_str())];
+
+        // Sending request
+        let resp = self
+            .client
+            .http
+            .put(&url)
+            .bearer_auth(&self.config.token)
+            .form(&params)
+            .send()
+            .await?;
+
+        if resp.status().is_success() {
+            let _: Value = resp.json().await?;
+            Ok(())
+        } else {
+            Err(Error::from_response(resp).await.context(format!(
+                "Can't post the grade of {}",
+                self.mapping.assignment_name(assignment)
+            )))
+        }
+    }
+
+    async fn record_results(
+        &self,
+        github_id: &str,
+        results: &TestResults,
+        status_values: &StatusValues,
+    ) -> Result<()> {
+        self.mapping
+            .record_results(self, github_id, results, status_values)
+            .await
+    }
+}
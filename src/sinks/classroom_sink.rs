@@ -0,0 +1,203 @@
+use crate::config::inputs::{optional_input, required_input, workspace_path};
+use crate::data_processing::roster::{load_mapping, parse_mapping};
+use crate::error::{Error, Result};
+use crate::google_sheets::api::send;
+use crate::google_sheets::token_provider::TokenProvider;
+use crate::http::client::ApiClient;
+use crate::sinks::grade_sink::GradeSink;
+use crate::sinks::id_mapping::{numeric_grade, IdMapping};
+use crate::utils::json_parser::TestResults;
+use crate::utils::status_values::StatusValues;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Scope needed to grade the coursework of a course.
+pub const CLASSROOM_SCOPE: &str = "https://www.googleapis.com/auth/classroom.coursework.students";
+
+/// Grade of the student submission that is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClassroomGrade {
+    /// `draftGrade`, only the teachers see it until they return the submission.
+    Draft,
+    /// `draftGrade` and `assignedGrade`, the student sees the grade.
+    Assigned,
+}
+
+impl ClassroomGrade {
+    fn update_mask(self) -> &'static str {
+        match self {
+            ClassroomGrade::Draft => "draftGrade",
+            ClassroomGrade::Assigned => "draftGrade,assignedGrade",
+        }
+    }
+}
+
+/// Google Classroom course the grades are set in.
+#[derive(Debug, Clone)]
+pub struct ClassroomConfig {
+    pub course_id: String,
+    pub grade: ClassroomGrade,
+    /// github_id -> email (or numeric id) of the student in Classroom.
+    pub roster: HashMap<String, String>,
+    /// Assignment (test) name -> courseWork id.
+    pub assignments: HashMap<String, String>,
+}
+
+impl ClassroomConfig {
+    /// Reads the `INPUT_CLASSROOM_*` inputs. The roster is a CSV file with `github_id`
+    /// (or `github_username`) and `email` columns.
+    pub fn from_inputs() -> Result<Self> {
+        let grade = match optional_input("INPUT_CLASSROOM_GRADE").as_deref().map(str::trim) {
+            None | Some("draft") => ClassroomGrade::Draft,
+            Some("assigned") => ClassroomGrade::Assigned,
+            Some(other) => {
+                return Err(Error::config(format!(
+                    "INPUT_CLASSROOM_GRADE must be 'draft' or 'assigned', got '{}'",
+                    other
+                )))
+            }
+        };
+
+        let roster_path = workspace_path(&required_input("INPUT_CLASSROOM_ROSTER")?);
+        Ok(ClassroomConfig {
+            course_id: required_input("INPUT_CLASSROOM_COURSE_ID")?.trim().to_string(),
+            grade,
+            roster: load_mapping(&roster_path, &["github_id", "github_username"], "email")?,
+            assignments: parse_mapping(&required_input("INPUT_CLASSROOM_ASSIGNMENTS")?, "INPUT_CLASSROOM_ASSIGNMENTS")?,
+        })
+    }
+}
+
+/// Sets the grades of Google Classroom coursework through the `studentSubmissions` API.
+///
+/// Uses the tokens of the Google accounts, with the [`CLASSROOM_SCOPE`] scope. Students and
+/// coursework are mapped like in Canvas, see [`IdMapping`].
+pub struct ClassroomGradeSink<'a> {
+    client: &'a ApiClient,
+    tokens: &'a dyn TokenProvider,
+    config: ClassroomConfig,
+    mapping: IdMapping,
+}
+
+impl<'a> ClassroomGradeSink<'a> {
+    pub fn new(client: &'a ApiClient, tokens: &'a dyn TokenProvider, config: ClassroomConfig) -> Self {
+        let mapping = IdMapping::new("Classroom", &config.roster, &config.assignments);
+        ClassroomGradeSink {
+            client,
+            tokens,
+            config,
+            mapping,
+        }
+    }
+
+    // The submission of the student, Classroom creates one for every student of the course
+    async fn submission(&self, student: usize, assignment: usize) -> Result<Value> {
+        let (user_id, course_work_id) = self.mapping.ids(student, assignment)?;
+
+        // URL
+        let url = self.client.classroom_url(&format!(
+            "/courses/{}/courseWork/{}/studentSubmissions",
+            self.config.course_id, course_work_id
+        ));
+
+        // Sending request
+        let request = self.client.http.get(&url).query(&[("userId", user_id)]);
+        let resp = send(self.tokens, request).await?;
+
+        if !resp.status().is_success() {
+            return Err(Error::from_response(resp).await.context(format!(
+                "Can't load the Classroom submission of {}",
+                user_id
+            )));
+        }
+        let data: Value = resp.json().await?;
+        data["studentSubmissions"]
+            .as_array()
+            .and_then(|submissions| submissions.first())
+            .cloned()
+            .ok_or_else(|| {
+                Error::not_found(format!(
+                    "{} has no submission of {} in the Classroom course",
+                    user_id,
+                    self.mapping.assignment_name(assignment)
+                ))
+            })
+    }
+}
+
+#[async_trait]
+impl GradeSink for ClassroomGradeSink<'_> {
+    async fn find_or_create_student(&self, github_id: &str) -> Result<usize> {
+        self.mapping.student(github_id)
+    }
+
+    async fn find_or_create_assignment(&self, assignment_name: &str) -> Result<usize> {
+        self.mapping.assignment(assignment_name)
+    }
+
+    async fn read_cell(&self, student: usize, assignment: usize) -> Result<Option<String>> {
+        let submission = self.submission(student, assignment).await?;
+        let field = match self.config.grade {
+            ClassroomGrade::Draft => "draftGrade",
+            ClassroomGrade::Assigned => "assignedGrade",
+        };
+        Ok(submission[field].as_f64().map(|grade| grade.to_string()))
+    }
+
+    async fn write_cell(&self, student: usize, assignment: usize, value: Value) -> Result<()> {
+        let grade = numeric_grade(&value, "Classroom")?;
+        let submission = self.submission(student, assignment).await?;
+        let submission_id = submission["id"]
+            .as_str()
+            .ok_or_else(|| Error::parse("Classroom submission without an id"))?;
+        let (_, course_work_id) = self.mapping.ids(student, assignment)?;
+
+        // URL
+        let url = self.client.classroom_url(&format!(
+            "/courses/{}/courseWork/{}/studentSubmissions/{}",
+            self.config.course_id, course_work_id, submission_id
+        ));
+
+        // Request body
+        let body = match self.config.grade {
+            ClassroomGrade::Draft => serde_json::json!({ "draftGrade": grade }),
+            ClassroomGrade::Assigned => serde_json::json!({ "draftGrade": grade, "assignedGrade": grade }),
+        };
+
+        // Sending request
+        let request = self
+            .client
+            .http
+            .patch(&url)
+            .query(&[("updateMask", self.config.grade.update_mask())])
+            .json(&body);
+        let resp = send(self.tokens, request).await?;
+
+        if resp.status().is_success() {
+            let _: Value = resp.json().await?;
+            Ok(())
+        } else {
+            let mut err = Error::from_response(resp).await;
+            // Only the developer project that created the coursework may grade it
+            if err.reason() == Some("ProjectPermissionDenied") {
+                err = err.context("the coursework must be created with the same Google Cloud project");
+            }
+            Err(err.context(format!(
+                "Can't set the grade of {}",
+                self.mapping.assignment_name(assignment)
+            )))
+        }
+    }
+
+    async fn record_results(
+        &self,
+        github_id: &str,
+        results: &TestResults,
+        status_values: &StatusValues,
+    ) -> Result<()> {
+        self.mapping
+            .record_results(self, github_id, results, status_values)
+            .await
+    }
+}
@@ -0,0 +1,54 @@
+use crate::error::{Error, Result};
+use crate::sinks::local_file::{io_error, replace_file};
+use crate::sinks::local_table::{LocalTableSink, TableFormat};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Gradebook kept in a local CSV file, see [`LocalTableSink`].
+pub type CsvGradeSink = LocalTableSink<CsvFormat>;
+
+impl CsvGradeSink {
+    /// The file is created on the first write if it does not exist.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        LocalTableSink::with_format(path, CsvFormat)
+    }
+}
+
+/// Rows of the table as CSV records, padded to the same width.
+pub struct CsvFormat;
+
+impl TableFormat for CsvFormat {
+    fn load(&self, path: &Path) -> Result<Vec<Vec<String>>> {
+        let content = match fs::read(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(io_error("read", path, e)),
+        };
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(content.as_slice());
+
+        let mut table = Vec::new();
+        for record in reader.records() {
+            let record = record.map_err(|e| Error::parse(format!("Invalid CSV in {}: {}", path.display(), e)))?;
+            table.push(record.iter().map(str::to_string).collect());
+        }
+        Ok(table)
+    }
+
+    fn save(&self, path: &Path, _before: &[Vec<String>], table: &[Vec<String>]) -> Result<()> {
+        let width = table.iter().map(Vec::len).max().unwrap_or(0);
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for row in table {
+            let mut record = row.clone();
+            record.resize(width, String::new());
+            writer.write_record(&record).map_err(|e| io_error("write", path, e))?;
+        }
+        let content = writer.into_inner().map_err(|e| io_error("write", path, e))?;
+
+        replace_file(path, |tmp_path| {
+            fs::write(tmp_path, content).map_err(|e| io_error("write", path, e))
+        })
+    }
+}
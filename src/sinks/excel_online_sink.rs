@@ -0,0 +1,116 @@
+use crate::data_processing::table::{
+    cell_text, changed_cells, get_cell, get_or_create_assignment_column, get_or_create_student_row, set_cell,
+};
+use crate::error::Result;
+use crate::google_sheets::token_provider::TokenProvider;
+use crate::http::client::ApiClient;
+use crate::microsoft_graph::api::{read_used_range, write_cells};
+use crate::sinks::grade_sink::GradeSink;
+use crate::utils::json_parser::TestResults;
+use crate::utils::status_values::StatusValues;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Gradebook in a worksheet of an Excel workbook in OneDrive or SharePoint, through Microsoft Graph.
+///
+/// The worksheet has the same layout as the Google sheet and rows and columns are found or
+/// added the same way as by [`StudentManager`]; only the cells that change are written.
+///
+/// [`StudentManager`]: crate::students::student_manager::StudentManager
+pub struct ExcelOnlineGradeSink<'a> {
+    pub client: &'a ApiClient,
+    pub tokens: &'a dyn TokenProvider,
+    /// Graph path of the workbook, e.g. `/drives/{drive-id}/items/{item-id}`.
+    pub workbook: &'a str,
+    pub sheet_name: &'a str,
+}
+
+impl<'a> ExcelOnlineGradeSink<'a> {
+    pub fn new(
+        client: &'a ApiClient,
+        tokens: &'a dyn TokenProvider,
+        workbook: &'a str,
+        sheet_name: &'a str,
+    ) -> Self {
+        ExcelOnlineGradeSink {
+            client,
+            tokens,
+            workbook,
+            sheet_name,
+        }
+    }
+
+    pub async fn read_table(&self) -> Result<Vec<Vec<String>>> {
+        read_used_range(self.client, self.tokens, self.workbook, self.sheet_name).await
+    }
+
+    // Applies `change` to the worksheet read just before and writes the cells that differ
+    async fn update<T>(
+        &self,
+        change: impl FnOnce(&mut Vec<Vec<String>>, &mut HashMap<(usize, usize), Value>) -> Result<T> + Send,
+    ) -> Result<T> {
+        let before = self.read_table().await?;
+        let mut table = before.clone();
+        let mut values = HashMap::new();
+        let result = change(&mut table, &mut values)?;
+
+        let cells = changed_cells(&before, &table, values);
+        if !cells.is_empty() {
+            write_cells(self.client, self.tokens, self.workbook, self.sheet_name, &cells).await?;
+        }
+        Ok(result)
+    }
+}
+
+#[async_trait]
+impl GradeSink for ExcelOnlineGradeSink<'_> {
+    async fn find_or_create_student(&self, github_id: &str) -> Result<usize> {
+        self.update(|table, _| get_or_create_student_row(table, github_id, self.sheet_name))
+            .await
+    }
+
+    async fn find_or_create_assignment(&self, assignment_name: &str) -> Result<usize> {
+        self.update(|table, _| Ok(get_or_create_assignment_column(table, assignment_name)))
+            .await
+    }
+
+    async fn read_cell(&self, student: usize, assignment: usize) -> Result<Option<String>> {
+        Ok(get_cell(&self.read_table().await?, student, assignment))
+    }
+
+    async fn write_cell(&self, student: usize, assignment: usize, value: Value) -> Result<()> {
+        self.write_cells(&[(student, assignment, value)]).await
+    }
+
+    async fn write_cells(&self, cells: &[(usize, usize, Value)]) -> Result<()> {
+        self.update(|table, values| {
+            for (student, assignment, value) in cells {
+                set_cell(table, *student, *assignment, cell_text(value));
+                values.insert((*student, *assignment), value.clone());
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    // The submission is laid out on one read of the worksheet and written in one batch
+    async fn record_results(
+        &self,
+        github_id: &str,
+        results: &TestResults,
+        status_values: &StatusValues,
+    ) -> Result<()> {
+        self.update(|table, values| {
+            let student = get_or_create_student_row(table, github_id, self.sheet_name)?;
+            for test in &results.tests {
+                let assignment = get_or_create_assignment_column(table, &test.name);
+                let value = status_values.cell_value(test);
+                set_cell(table, student, assignment, cell_text(&value));
+                values.insert((student, assignment), value);
+            }
+            Ok(())
+        })
+        .await
+    }
+}
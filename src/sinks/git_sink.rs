@@ -0,0 +1,270 @@
+use crate::config::inputs::{optional_input, required_input, workspace_path};
+use crate::error::{Error, ErrorKind, Result};
+use crate::sinks::csv_sink::CsvGradeSink;
+use crate::sinks::grade_sink::GradeSink;
+use crate::sinks::json_sink::JsonGradeSink;
+use crate::sinks::local_file::io_error;
+use crate::utils::json_parser::TestResults;
+use crate::utils::status_values::StatusValues;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Edit of the gradebook file, run again on top of the remote after a rejected push
+type Change<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Clone of the grades repository and where the gradebook is pushed.
+#[derive(Debug, Clone)]
+pub struct GitConfig {
+    /// Local clone, with the remote configured.
+    pub repo_path: PathBuf,
+    /// Gradebook in the repository, `.json` files are JSON gradebooks, the others CSV.
+    pub file: String,
+    pub remote: String,
+    pub branch: String,
+    /// Attempts after a push rejected because another job pushed first.
+    pub push_retries: u32,
+    pub author_name: String,
+    pub author_email: String,
+}
+
+impl GitConfig {
+    /// Reads the `INPUT_GIT_*` inputs, only the path of the clone is required.
+    pub fn from_inputs() -> Result<Self> {
+        let push_retries = match optional_input("INPUT_GIT_PUSH_RETRIES") {
+            Some(retries) => retries.trim().parse().map_err(|_| {
+                Error::config(format!("INPUT_GIT_PUSH_RETRIES must be a number, got '{}'", retries))
+            })?,
+            None => 10,
+        };
+        Ok(GitConfig {
+            repo_path: workspace_path(&required_input("INPUT_GIT_REPO_PATH")?),
+            file: optional_input("INPUT_GIT_FILE").unwrap_or_else(|| "grades.csv".to_string()),
+            remote: optional_input("INPUT_GIT_REMOTE").unwrap_or_else(|| "origin".to_string()),
+            branch: optional_input("INPUT_GIT_BRANCH").unwrap_or_else(|| "main".to_string()),
+            push_retries,
+            author_name: optional_input("INPUT_GIT_AUTHOR_NAME")
+                .unwrap_or_else(|| "github-classroom-grades".to_string()),
+            author_email: optional_input("INPUT_GIT_AUTHOR_EMAIL")
+                .unwrap_or_else(|| "github-classroom-grades@users.noreply.github.com".to_string()),
+        })
+    }
+}
+
+// Outcome of `git push`
+enum Push {
+    Pushed,
+    Rejected,
+}
+
+/// Gradebook file in a git repository: every change is a commit pushed to the remote.
+///
+/// Before a change the clone is reset to the remote branch. When the push is rejected because
+/// another job pushed in the meantime, the change is rebased: the clone is reset to the new
+/// remote head and the change is applied and committed again, up to `push_retries` times.
+/// Rebasing by replaying the change never conflicts, even when both jobs added a row.
+pub struct GitGradeSink {
+    config: GitConfig,
+    gradebook: Box<dyn GradeSink>,
+}
+
+impl GitGradeSink {
+    pub fn new(config: GitConfig) -> Self {
+        let path = config.repo_path.join(&config.file);
+        // A lock next to the gradebook would be left in the work tree as an untracked file
+        let lock_path = config.repo_path.join(".git").join("gradebook.lock");
+        let gradebook: Box<dyn GradeSink> = if config.file.ends_with(".json") {
+            Box::new(JsonGradeSink::new(path).locked_by(lock_path))
+        } else {
+            Box::new(CsvGradeSink::new(path).locked_by(lock_path))
+        };
+        GitGradeSink { config, gradebook }
+    }
+
+    // The action runs as root in a container, the clone belongs to the runner user: without
+    // `safe.directory` git refuses to work in it ("detected dubious ownership")
+    fn git(&self, args: &[&str]) -> Result<std::process::Output> {
+        Command::new("git")
+            .arg("-C")
+            .arg(&self.config.repo_path)
+            .args(args)
+            .env("GIT_CONFIG_COUNT", "1")
+            .env("GIT_CONFIG_KEY_0", "safe.directory")
+            .env("GIT_CONFIG_VALUE_0", &self.config.repo_path)
+            .env("GIT_AUTHOR_NAME", &self.config.author_name)
+            .env("GIT_AUTHOR_EMAIL", &self.config.author_email)
+            .env("GIT_COMMITTER_NAME", &self.config.author_name)
+            .env("GIT_COMMITTER_EMAIL", &self.config.author_email)
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .output()
+            .map_err(|e| Error::config(format!("Can't run git: {}", e)))
+    }
+
+    // Runs git and fails with its error output if it fails
+    fn run_git(&self, args: &[&str]) -> Result<String> {
+        let output = self.git(args)?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            Err(git_error(args, &output))
+        }
+    }
+
+    // Moves the clone to the head of the remote branch, a new branch starts from the current commit
+    fn reset_to_remote(&self) -> Result<()> {
+        if !self.config.repo_path.join(".git").exists() {
+            return Err(Error::config(format!(
+                "{} is not a clone of the grades repository",
+                self.config.repo_path.display()
+            )));
+        }
+        let fetch = self.git(&["fetch", &self.config.remote, &self.config.branch])?;
+        if fetch.status.success() {
+            self.run_git(&["checkout", "--force", "-B", &self.config.branch, "FETCH_HEAD"])?;
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&fetch.stderr);
+        if stderr.contains("couldn't find remote ref") {
+            self.run_git(&["checkout", "--force", "-B", &self.config.branch])?;
+            Ok(())
+        } else {
+            Err(git_error(&["fetch"], &fetch))
+        }
+    }
+
+    fn push(&self) -> Result<Push> {
+        let refspec = format!("HEAD:refs/heads/{}", self.config.branch);
+        let output = self.git(&["push", &self.config.remote, &refspec])?;
+        if output.status.success() {
+            return Ok(Push::Pushed);
+        }
+        // Another push got there first, or was updating the branch at the same moment
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let outdated = ["[rejected]", "non-fast-forward", "fetch first", "cannot lock ref", "failed to update ref"];
+        if outdated.iter().any(|reason| stderr.contains(reason)) {
+            Ok(Push::Rejected)
+        } else {
+            Err(git_error(&["push"], &output))
+        }
+    }
+
+    // Applies the change on top of the remote branch, commits it and pushes, retrying on rejection
+    async fn commit<'a, T: Send>(
+        &'a self,
+        message: &str,
+        change: impl Fn() -> Change<'a, T> + Send + Sync,
+    ) -> Result<T> {
+        for attempt in 0..=self.config.push_retries {
+            self.reset_to_remote()?;
+            // The folder of a new gradebook is not in the repository yet
+            let path = self.config.repo_path.join(&self.config.file);
+            if let Some(folder) = path.parent() {
+                fs::create_dir_all(folder).map_err(|e| io_error("create the folder of", &path, e))?;
+            }
+            let result = change().await?;
+
+            let status = self.run_git(&["status", "--porcelain", "--", &self.config.file])?;
+            if status.trim().is_empty() {
+                return Ok(result);
+            }
+            self.run_git(&["add", "--", &self.config.file])?;
+            self.run_git(&["commit", "--quiet", "-m", message])?;
+
+            match self.push()? {
+                Push::Pushed => return Ok(result),
+                Push::Rejected if attempt < self.config.push_retries => {
+                    eprintln!("The push was rejected, applying the change on top of the new commits");
+                    tokio::time::sleep(retry_delay(attempt)).await;
+                }
+                Push::Rejected => {}
+            }
+        }
+        Err(Error::new(
+            ErrorKind::Api,
+            format!(
+                "The push to {} was rejected {} times, other jobs kept pushing first",
+                self.config.remote,
+                self.config.push_retries + 1
+            ),
+        ))
+    }
+}
+
+// Jobs of one workflow run finish together: a random delay up to an exponentially growing
+// limit spreads their retries
+fn retry_delay(attempt: u32) -> Duration {
+    let limit = 250u64 << attempt.min(5);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.subsec_nanos())
+        .unwrap_or(0);
+    let seed = u64::from(nanos) ^ u64::from(std::process::id()).wrapping_mul(2_654_435_761);
+    Duration::from_millis(seed % limit)
+}
+
+fn git_error(args: &[&str], output: &std::process::Output) -> Error {
+    Error::new(
+        ErrorKind::Api,
+        format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+    )
+}
+
+// Message of the commit of a submission, with the run that produced it for the audit trail
+fn commit_message(github_id: &str) -> String {
+    let mut message = format!("Record the results of {}", github_id);
+    if let (Ok(repository), Ok(sha)) = (std::env::var("GITHUB_REPOSITORY"), std::env::var("GITHUB_SHA")) {
+        message.push_str(&format!("\n\nRepository: {}\nCommit: {}", repository, sha));
+    }
+    message
+}
+
+#[async_trait]
+impl GradeSink for GitGradeSink {
+    async fn find_or_create_student(&self, github_id: &str) -> Result<usize> {
+        let message = format!("Add {}", github_id);
+        self.commit(&message, || self.gradebook.find_or_create_student(github_id))
+            .await
+    }
+
+    async fn find_or_create_assignment(&self, assignment_name: &str) -> Result<usize> {
+        let message = format!("Add {}", assignment_name);
+        self.commit(&message, || self.gradebook.find_or_create_assignment(assignment_name))
+            .await
+    }
+
+    // Reads the clone as it is, without fetching
+    async fn read_cell(&self, student: usize, assignment: usize) -> Result<Option<String>> {
+        self.gradebook.read_cell(student, assignment).await
+    }
+
+    async fn write_cell(&self, student: usize, assignment: usize, value: Value) -> Result<()> {
+        self.write_cells(&[(student, assignment, value)]).await
+    }
+
+    async fn write_cells(&self, cells: &[(usize, usize, Value)]) -> Result<()> {
+        self.commit("Update grades", || self.gradebook.write_cells(cells))
+            .await
+    }
+
+    // One commit per submission
+    async fn record_results(
+        &self,
+        github_id: &str,
+        results: &TestResults,
+        status_values: &StatusValues,
+    ) -> Result<()> {
+        self.commit(&commit_message(github_id), || {
+            self.gradebook.record_results(github_id, results, status_values)
+        })
+        .await
+    }
+}
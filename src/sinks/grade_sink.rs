@@ -0,0 +1,58 @@
+use crate::error::Result;
+use crate::utils::json_parser::TestResults;
+use crate::utils::status_values::StatusValues;
+use async_trait::async_trait;
+use serde_json::Value;
+
+/// Storage of the gradebook: one record per student, one field per assignment.
+///
+/// Students and assignments are addressed by the index the sink returns for them,
+/// e.g. the row and the column of a sheet.
+#[async_trait]
+pub trait GradeSink: Send + Sync {
+    /// Returns the index of the student, adding the student if it is not there yet.
+    async fn find_or_create_student(&self, github_id: &str) -> Result<usize>;
+
+    /// Returns the index of the assignment, adding the assignment if it is not there yet.
+    async fn find_or_create_assignment(&self, assignment_name: &str) -> Result<usize>;
+
+    async fn read_cell(&self, student: usize, assignment: usize) -> Result<Option<String>>;
+
+    async fn write_cell(&self, student: usize, assignment: usize, value: Value) -> Result<()>;
+
+    /// Writes several values, sinks that can do it in one request override this.
+    async fn write_cells(&self, cells: &[(usize, usize, Value)]) -> Result<()> {
+        for (student, assignment, value) in cells {
+            self.write_cell(*student, *assignment, value.clone()).await?;
+        }
+        Ok(())
+    }
+
+    /// Writes the result of the student for the assignment, creating both if needed.
+    async fn update_assignment_result(
+        &self,
+        github_id: &str,
+        assignment_name: &str,
+        result: Value,
+    ) -> Result<()> {
+        let student = self.find_or_create_student(github_id).await?;
+        let assignment = self.find_or_create_assignment(assignment_name).await?;
+        self.write_cell(student, assignment, result).await
+    }
+
+    /// Writes every test of the submission into the assignment named after the test.
+    async fn record_results(
+        &self,
+        github_id: &str,
+        results: &TestResults,
+        status_values: &StatusValues,
+    ) -> Result<()> {
+        let student = self.find_or_create_student(github_id).await?;
+        let mut cells = Vec::new();
+        for test in &results.tests {
+            let assignment = self.find_or_create_assignment(&test.name).await?;
+            cells.push((student, assignment, status_values.cell_value(test)));
+        }
+        self.write_cells(&cells).await
+    }
+}
@@ -0,0 +1,124 @@
+use crate::error::{Error, Result};
+use crate::sinks::grade_sink::GradeSink;
+use crate::utils::json_parser::TestResults;
+use crate::utils::status_values::StatusValues;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Students and assignments of a gradebook that can't create them (an LMS course),
+/// looked up in a roster and an assignment map and addressed by their position in them.
+#[derive(Debug, Clone)]
+pub struct IdMapping {
+    /// Name of the gradebook in the error messages, e.g. "Canvas".
+    target: &'static str,
+    students: Vec<(String, String)>,
+    assignments: Vec<(String, String)>,
+}
+
+impl IdMapping {
+    pub fn new(
+        target: &'static str,
+        roster: &HashMap<String, String>,
+        assignments: &HashMap<String, String>,
+    ) -> Self {
+        let mut students: Vec<(String, String)> = roster.clone().into_iter().collect();
+        students.sort();
+        let mut assignments: Vec<(String, String)> = assignments.clone().into_iter().collect();
+        assignments.sort();
+        IdMapping {
+            target,
+            students,
+            assignments,
+        }
+    }
+
+    pub fn student(&self, github_id: &str) -> Result<usize> {
+        self.students
+            .iter()
+            .position(|(id, _)| id == github_id)
+            .ok_or_else(|| Error::not_found(format!("'{}' is not in the {} roster", github_id, self.target)))
+    }
+
+    pub fn assignment(&self, assignment_name: &str) -> Result<usize> {
+        self.assignments
+            .iter()
+            .position(|(name, _)| name == assignment_name)
+            .ok_or_else(|| {
+                Error::not_found(format!(
+                    "'{}' is not in the {} assignment map",
+                    assignment_name, self.target
+                ))
+            })
+    }
+
+    /// Ids of the student and the assignment in the gradebook.
+    pub fn ids(&self, student: usize, assignment: usize) -> Result<(&str, &str)> {
+        let (_, user_id) = self.students.get(student).ok_or_else(|| {
+            Error::not_found(format!("No student {} in the {} roster", student, self.target))
+        })?;
+        let (_, assignment_id) = self.assignments.get(assignment).ok_or_else(|| {
+            Error::not_found(format!("No assignment {} in the {} assignment map", assignment, self.target))
+        })?;
+        Ok((user_id, assignment_id))
+    }
+
+    pub fn assignment_name(&self, assignment: usize) -> &str {
+        self.assignments
+            .get(assignment)
+            .map(|(name, _)| name.as_str())
+            .unwrap_or_default()
+    }
+
+    /// Writes the tests that are in the assignment map, the others are skipped with a notice:
+    /// not every test has to be graded in the LMS. Fails if none of the tests is mapped.
+    pub async fn record_results(
+        &self,
+        sink: &dyn GradeSink,
+        github_id: &str,
+        results: &TestResults,
+        status_values: &StatusValues,
+    ) -> Result<()> {
+        let student = self.student(github_id)?;
+        let mut skipped = Vec::new();
+        for test in &results.tests {
+            match self.assignment(&test.name) {
+                Ok(assignment) => {
+                    sink.write_cell(student, assignment, status_values.cell_value(test))
+                        .await?
+                }
+                Err(_) => skipped.push(test.name.as_str()),
+            }
+        }
+
+        if skipped.len() == results.tests.len() && !skipped.is_empty() {
+            return Err(Error::config(format!(
+                "None of the tests is in the {} assignment map: {}",
+                self.target,
+                skipped.join(", ")
+            )));
+        }
+        if !skipped.is_empty() {
+            eprintln!(
+                "Not in the {} assignment map, skipped: {}",
+                self.target,
+                skipped.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Grade of a cell value for an LMS whose grades are numbers: pass and fail need numeric status values.
+pub fn numeric_grade(value: &Value, target: &str) -> Result<f64> {
+    let grade = match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) => text.trim().parse().ok(),
+        _ => None,
+    };
+    grade.ok_or_else(|| {
+        Error::config(format!(
+            "{} grades must be numbers, got {} (check the status values)",
+            target, value
+        ))
+    })
+}
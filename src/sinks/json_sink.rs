@@ -0,0 +1,82 @@
+use crate::data_processing::table::cell_text;
+use crate::data_processing::utils::find_column_by_header;
+use crate::error::{Error, Result};
+use crate::sinks::local_file::{io_error, replace_file};
+use crate::sinks::local_table::{LocalTableSink, TableFormat};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// {"columns": ["github_id", "test-1"], "rows": [["octocat", 1]]}
+#[derive(Debug, Deserialize)]
+struct JsonGradebook {
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+}
+
+/// Gradebook kept in a local JSON file, with the same columns and rows as the Google sheet,
+/// see [`LocalTableSink`]. Grades that are numbers are stored as JSON numbers.
+pub type JsonGradeSink = LocalTableSink<JsonFormat>;
+
+impl JsonGradeSink {
+    /// The file is created on the first write if it does not exist.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        LocalTableSink::with_format(path, JsonFormat)
+    }
+}
+
+/// `{"columns": [...], "rows": [[...], ...]}` with one row per line.
+pub struct JsonFormat;
+
+impl TableFormat for JsonFormat {
+    fn load(&self, path: &Path) -> Result<Vec<Vec<String>>> {
+        let content = match fs::read(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(io_error("read", path, e)),
+        };
+        let gradebook: JsonGradebook = serde_json::from_slice(&content)
+            .map_err(|e| Error::parse(format!("Invalid gradebook {}: {}", path.display(), e)))?;
+
+        let mut table = vec![gradebook.columns];
+        for row in &gradebook.rows {
+            table.push(row.iter().map(cell_text).collect());
+        }
+        Ok(table)
+    }
+
+    fn save(&self, path: &Path, _before: &[Vec<String>], table: &[Vec<String>]) -> Result<()> {
+        let width = table.iter().map(Vec::len).max().unwrap_or(0);
+        let github_id_col = find_column_by_header(table, "github_id");
+
+        let mut columns = table.first().cloned().unwrap_or_default();
+        columns.resize(width, String::new());
+
+        // One student per line, so that the history of a grades repository diffs by student
+        let mut rows = Vec::new();
+        for row in table.iter().skip(1) {
+            let values: Vec<Value> = (0..width)
+                .map(|col| {
+                    let text = row.get(col).map(String::as_str).unwrap_or("");
+                    match serde_json::from_str::<Value>(text) {
+                        // github ids stay strings even if they look like numbers
+                        Ok(Value::Number(number)) if Some(col) != github_id_col => Value::Number(number),
+                        _ if text.is_empty() => Value::Null,
+                        _ => Value::String(text.to_string()),
+                    }
+                })
+                .collect();
+            rows.push(format!("    {}", serde_json::to_string(&values)?));
+        }
+        let content = format!(
+            "{{\n  \"columns\": {},\n  \"rows\": [\n{}\n  ]\n}}\n",
+            serde_json::to_string(&columns)?,
+            rows.join(",\n")
+        );
+
+        replace_file(path, |tmp_path| {
+            fs::write(tmp_path, content).map_err(|e| io_error("write", path, e))
+        })
+    }
+}
@@ -0,0 +1,50 @@
+use crate::error::{Error, Result};
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+// Helpers of the gradebooks kept in local files
+
+/// Lock file of the gradebook `path`, `<path>.lock`.
+///
+/// The lock is on a separate file because the gradebook itself is replaced on every save.
+pub fn lock_path(path: &Path) -> PathBuf {
+    sibling(path, ".lock")
+}
+
+/// Takes an exclusive lock of `lock_path` for the gradebook `path`, released when the returned file is closed.
+pub fn lock_file(lock_path: &Path, path: &Path) -> Result<File> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path)
+        .map_err(|e| io_error("lock", path, e))?;
+    file.lock().map_err(|e| io_error("lock", path, e))?;
+    Ok(file)
+}
+
+/// Lets `write` create `<path>.tmp` and renames it over `path`, readers never see a partial file.
+pub fn replace_file(path: &Path, write: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+    let tmp_path = sibling(path, ".tmp");
+    let result = write(&tmp_path).and_then(|()| {
+        File::open(&tmp_path)
+            .and_then(|file| file.sync_all())
+            .and_then(|()| fs::rename(&tmp_path, path))
+            .map_err(|e| io_error("write", path, e))
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+pub fn io_error(action: &str, path: &Path, err: impl std::fmt::Display) -> Error {
+    Error::config(format!("Can't {} {}: {}", action, path.display(), err))
+}
+
+// `grades.csv` -> `grades.csv.lock`
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
@@ -0,0 +1,116 @@
+use crate::data_processing::table::{
+    cell_text, get_cell, get_or_create_assignment_column, get_or_create_student_row, set_cell,
+};
+use crate::error::Result;
+use crate::sinks::grade_sink::GradeSink;
+use crate::sinks::local_file::{lock_file, lock_path};
+use crate::utils::json_parser::TestResults;
+use crate::utils::status_values::StatusValues;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// File format of a gradebook kept in a local file, read and written as the table of cell texts.
+pub trait TableFormat: Send + Sync {
+    /// Reads the table, a missing file is an empty table.
+    fn load(&self, path: &Path) -> Result<Vec<Vec<String>>>;
+
+    /// Replaces the file with `table`; `before` is the table loaded under the same lock.
+    fn save(&self, path: &Path, before: &[Vec<String>], table: &[Vec<String>]) -> Result<()>;
+
+    /// Name of the gradebook in the error messages.
+    fn table_name(&self, path: &Path) -> String {
+        path.display().to_string()
+    }
+}
+
+/// Gradebook kept in a local file, with the same layout as the Google sheet.
+///
+/// Every change locks `<file>.lock` (or the file given to [`LocalTableSink::locked_by`]),
+/// rereads the file and replaces it atomically, so several jobs can share the file.
+pub struct LocalTableSink<F> {
+    path: PathBuf,
+    lock_path: PathBuf,
+    format: F,
+}
+
+impl<F: TableFormat> LocalTableSink<F> {
+    /// The file is created on the first write if it does not exist.
+    pub fn with_format(path: impl Into<PathBuf>, format: F) -> Self {
+        let path = path.into();
+        LocalTableSink {
+            lock_path: lock_path(&path),
+            path,
+            format,
+        }
+    }
+
+    /// Locks `lock_path` instead of `<file>.lock`, e.g. to keep the lock out of a git work tree.
+    pub fn locked_by(mut self, lock_path: impl Into<PathBuf>) -> Self {
+        self.lock_path = lock_path.into();
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Holds an exclusive lock while `change` edits the table, then saves it
+    fn update<T>(&self, change: impl FnOnce(&mut Vec<Vec<String>>) -> Result<T>) -> Result<T> {
+        let _lock = lock_file(&self.lock_path, &self.path)?;
+        let before = self.format.load(&self.path)?;
+        let mut table = before.clone();
+        let result = change(&mut table)?;
+        if table != before {
+            self.format.save(&self.path, &before, &table)?;
+        }
+        Ok(result)
+    }
+}
+
+#[async_trait]
+impl<F: TableFormat> GradeSink for LocalTableSink<F> {
+    async fn find_or_create_student(&self, github_id: &str) -> Result<usize> {
+        let table_name = self.format.table_name(&self.path);
+        self.update(|table| get_or_create_student_row(table, github_id, &table_name))
+    }
+
+    async fn find_or_create_assignment(&self, assignment_name: &str) -> Result<usize> {
+        self.update(|table| Ok(get_or_create_assignment_column(table, assignment_name)))
+    }
+
+    async fn read_cell(&self, student: usize, assignment: usize) -> Result<Option<String>> {
+        Ok(get_cell(&self.format.load(&self.path)?, student, assignment))
+    }
+
+    async fn write_cell(&self, student: usize, assignment: usize, value: Value) -> Result<()> {
+        self.write_cells(&[(student, assignment, value)]).await
+    }
+
+    async fn write_cells(&self, cells: &[(usize, usize, Value)]) -> Result<()> {
+        self.update(|table| {
+            for (student, assignment, value) in cells {
+                set_cell(table, *student, *assignment, cell_text(value));
+            }
+            Ok(())
+        })
+    }
+
+    // The whole submission is written under one lock and in one save
+    async fn record_results(
+        &self,
+        github_id: &str,
+        results: &TestResults,
+        status_values: &StatusValues,
+    ) -> Result<()> {
+        let table_name = self.format.table_name(&self.path);
+        self.update(|table| {
+            let student = get_or_create_student_row(table, github_id, &table_name)?;
+            for test in &results.tests {
+                let assignment = get_or_create_assignment_column(table, &test.name);
+                set_cell(table, student, assignment, cell_text(&status_values.cell_value(test)));
+            }
+            Ok(())
+        })
+    }
+}
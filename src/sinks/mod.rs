@@ -0,0 +1,16 @@
+pub mod canvas_sink;
+pub mod classroom_sink;
+pub mod csv_sink;
+pub mod excel_online_sink;
+pub mod git_sink;
+pub mod grade_sink;
+pub mod id_mapping;
+pub mod json_sink;
+pub mod local_file;
+pub mod local_table;
+pub mod moodle_sink;
+#[cfg(feature = "postgres")]
+pub mod postgres_store;
+pub mod sqlite_store;
+pub mod webhook_sink;
+pub mod xlsx_sink;
@@ -0,0 +1,232 @@
+use crate::config::inputs::{optional_input, required_input, workspace_path};
+use crate::data_processing::roster::{load_mapping, parse_mapping};
+use crate::error::{Error, ErrorKind, Result};
+use crate::http::client::ApiClient;
+use crate::sinks::grade_sink::GradeSink;
+use crate::sinks::id_mapping::{numeric_grade, IdMapping};
+use crate::utils::json_parser::TestResults;
+use crate::utils::status_values::StatusValues;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Web-service function used to store a grade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoodleGradeFunction {
+    /// `mod_assign_save_grade`, the assignment map holds assignment instance ids.
+    AssignSaveGrade,
+    /// `core_grades_update_grades`, the assignment map holds course module ids of the activities.
+    UpdateGrades,
+}
+
+/// Moodle course the grades are pushed to.
+#[derive(Debug, Clone)]
+pub struct MoodleConfig {
+    /// Address of the Moodle site, e.g. `https://moodle.university.edu`.
+    pub base_url: String,
+    /// Web-service token of a user allowed to grade.
+    pub token: String,
+    pub function: MoodleGradeFunction,
+    /// Needed by `core_grades_update_grades`.
+    pub course_id: Option<String>,
+    /// Component of the activities for `core_grades_update_grades`, `mod_assign` by default.
+    pub component: String,
+    /// github_id -> Moodle user id.
+    pub roster: HashMap<String, String>,
+    /// Assignment (test) name -> Moodle id, see [`MoodleGradeFunction`].
+    pub assignments: HashMap<String, String>,
+}
+
+impl MoodleConfig {
+    /// Reads the `INPUT_MOODLE_*` inputs. The roster is a CSV file with `github_id`
+    /// (or `github_username`) and `moodle_user_id` columns.
+    pub fn from_inputs() -> Result<Self> {
+        let function = match optional_input("INPUT_MOODLE_FUNCTION").as_deref().map(str::trim) {
+            None | Some("mod_assign_save_grade") => MoodleGradeFunction::AssignSaveGrade,
+            Some("core_grades_update_grades") => MoodleGradeFunction::UpdateGrades,
+            Some(other) => {
+                return Err(Error::config(format!(
+                    "Unknown INPUT_MOODLE_FUNCTION '{}', expected 'mod_assign_save_grade' or 'core_grades_update_grades'",
+                    other
+                )))
+            }
+        };
+        let course_id = optional_input("INPUT_MOODLE_COURSE_ID").map(|id| id.trim().to_string());
+        if function == MoodleGradeFunction::UpdateGrades && course_id.is_none() {
+            return Err(Error::config("core_grades_update_grades needs INPUT_MOODLE_COURSE_ID"));
+        }
+
+        let roster_path = workspace_path(&required_input("INPUT_MOODLE_ROSTER")?);
+        Ok(MoodleConfig {
+            base_url: required_input("INPUT_MOODLE_BASE_URL")?.trim().trim_end_matches('/').to_string(),
+            token: required_input("INPUT_MOODLE_TOKEN")?.trim().to_string(),
+            function,
+            course_id,
+            component: optional_input("INPUT_MOODLE_COMPONENT").unwrap_or_else(|| "mod_assign".to_string()),
+            roster: load_mapping(&roster_path, &["github_id", "github_username"], "moodle_user_id")?,
+            assignments: parse_mapping(
+                &required_input("INPUT_MOODLE_ASSIGNMENTS")?,
+                "INPUT_MOODLE_ASSIGNMENTS",
+            )?,
+        })
+    }
+}
+
+/// Pushes grades to Moodle through its REST web services with token authentication.
+///
+/// Like in Canvas, students and assignments come from the roster and the assignment map,
+/// see [`IdMapping`].
+pub struct MoodleGradeSink {
+    client: ApiClient,
+    config: MoodleConfig,
+    mapping: IdMapping,
+}
+
+impl MoodleGradeSink {
+    pub fn new(client: ApiClient, config: MoodleConfig) -> Self {
+        let mapping = IdMapping::new("Moodle", &config.roster, &config.assignments);
+        MoodleGradeSink {
+            client,
+            config,
+            mapping,
+        }
+    }
+
+    // Calls a web-service function, Moodle reports errors in the body of a successful response
+    async fn call(&self, function: &str, params: &[(&str, &str)]) -> Result<Value> {
+        // URL
+        let url = format!("{}/webservice/rest/server.php", self.config.base_url);
+
+        // Request body
+        let mut form = vec![
+            ("wstoken", self.config.token.as_str()),
+            ("wsfunction", function),
+            ("moodlewsrestformat", "json"),
+        ];
+        form.extend_from_slice(params);
+
+        // Sending request
+        let resp = self.client.http.post(&url).form(&form).send().await?;
+
+        if !resp.status().is_success() {
+            return Err(Error::from_response(resp).await.context(format!("Can't call {}", function)));
+        }
+        let data: Value = resp.json().await?;
+        match data.get("exception") {
+            Some(_) => Err(moodle_error(&data).context(format!("Can't call {}", function))),
+            None => Ok(data),
+        }
+    }
+}
+
+// {"exception": "moodle_exception", "errorcode": "invalidtoken", "message": "Invalid token - token not found"}
+fn moodle_error(data: &Value) -> Error {
+    let errorcode = data["errorcode"].as_str().unwrap_or("");
+    let kind = match errorcode {
+        "invalidtoken" | "accessexception" => ErrorKind::Auth,
+        "nopermissions" | "requireloginerror" | "servicerequireslogin" => ErrorKind::Permission,
+        "invalidrecord" | "invalidrecordunknown" | "invaliduser" => ErrorKind::NotFound,
+        _ => ErrorKind::Api,
+    };
+    let message = data["message"].as_str().unwrap_or("unknown Moodle error");
+    Error::new(kind, format!("{} ({})", message, errorcode))
+}
+
+#[async_trait]
+impl GradeSink for MoodleGradeSink {
+    async fn find_or_create_student(&self, github_id: &str) -> Result<usize> {
+        self.mapping.student(github_id)
+    }
+
+    async fn find_or_create_assignment(&self, assignment_name: &str) -> Result<usize> {
+        self.mapping.assignment(assignment_name)
+    }
+
+    async fn read_cell(&self, student: usize, assignment: usize) -> Result<Option<String>> {
+        if self.config.function != MoodleGradeFunction::AssignSaveGrade {
+            return Err(Error::config("Reading grades needs the mod_assign_save_grade mapping"));
+        }
+        let (user_id, assignment_id) = self.mapping.ids(student, assignment)?;
+        let data = self
+            .call("mod_assign_get_grades", &[("assignmentids[0]", assignment_id)])
+            .await?;
+
+        let grade = data["assignments"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|assignment| assignment["grades"].as_array().into_iter().flatten())
+            .filter(|grade| match &grade["userid"] {
+                Value::Number(id) => id.to_string() == user_id,
+                Value::String(id) => id == user_id,
+                _ => false,
+            })
+            .filter_map(|grade| grade["grade"].as_str().map(str::to_string))
+            .next_back();
+        Ok(grade)
+    }
+
+    async fn write_cell(&self, student: usize, assignment: usize, value: Value) -> Result<()> {
+        let (user_id, assignment_id) = self.mapping.ids(student, assignment)?;
+        let grade = numeric_grade(&value, "Moodle")?.to_string();
+
+        match self.config.function {
+            MoodleGradeFunction::AssignSaveGrade => {
+                self.call(
+                    "mod_assign_save_grade",
+                    &[
+                        ("assignmentid", assignment_id),
+                        ("userid", user_id),
+                        ("grade", &grade),
+                        // -1 grades the latest attempt
+                        ("attemptnumber", "-1"),
+                        ("addattempt", "0"),
+                        ("workflowstate", ""),
+                        ("applytoall", "0"),
+                    ],
+                )
+                    .await?;
+            }
+            MoodleGradeFunction::UpdateGrades => {
+                let course_id = self.config.course_id.as_deref().unwrap_or_default();
+                let data = self
+                    .call(
+                        "core_grades_update_grades",
+                        &[
+                            ("source", "github-classroom"),
+                            ("courseid", course_id),
+                            ("component", &self.config.component),
+                            ("activityid", assignment_id),
+                            ("itemnumber", "0"),
+                            ("grades[0][studentid]", user_id),
+                            ("grades[0][grade]", &grade),
+                        ],
+                    )
+                    .await?;
+                // GRADE_UPDATE_OK is 0, anything else is a failure
+                if data.as_i64().is_some_and(|status| status != 0) {
+                    return Err(Error::new(
+                        ErrorKind::Api,
+                        format!(
+                            "Moodle refused the grade of {} (status {})",
+                            self.mapping.assignment_name(assignment),
+                            data
+                        ),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn record_results(
+        &self,
+        github_id: &str,
+        results: &TestResults,
+        status_values: &StatusValues,
+    ) -> Result<()> {
+        self.mapping
+            .record_results(self, github_id, results, status_values)
+            .await
+    }
+}
@@ -0,0 +1,372 @@
+use crate::data_processing::table::cell_text;
+use crate::error::{Error, Result};
+use crate::sinks::grade_sink::GradeSink;
+use crate::students::student_manager::StudentManager;
+use crate::utils::json_parser::TestResults;
+use crate::utils::status_values::StatusValues;
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Schema versions, the index + 1 is stored in `PRAGMA user_version`.
+// Never edit a released migration, append a new one.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE students (
+        id INTEGER PRIMARY KEY,
+        github_id TEXT NOT NULL UNIQUE,
+        created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE TABLE assignments (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE,
+        created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE TABLE submissions (
+        id INTEGER PRIMARY KEY,
+        student_id INTEGER NOT NULL REFERENCES students(id),
+        status TEXT NOT NULL,
+        max_score REAL,
+        repository TEXT,
+        commit_sha TEXT,
+        created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE TABLE test_results (
+        id INTEGER PRIMARY KEY,
+        submission_id INTEGER NOT NULL REFERENCES submissions(id),
+        assignment_id INTEGER NOT NULL REFERENCES assignments(id),
+        status TEXT NOT NULL,
+        score REAL,
+        points REAL,
+        duration INTEGER
+    );
+    CREATE INDEX test_results_submission ON test_results(submission_id);",
+    // Current value of every student x assignment cell, the state mirrored to the sheet
+    "CREATE TABLE grades (
+        student_id INTEGER NOT NULL REFERENCES students(id),
+        assignment_id INTEGER NOT NULL REFERENCES assignments(id),
+        value TEXT NOT NULL,
+        updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY (student_id, assignment_id)
+    );",
+];
+
+/// Gradebook kept in a SQLite database: students, assignments, every submission with its
+/// test results, and the current grade of each student for each assignment.
+///
+/// The database is the source of truth, [`SqliteGradeStore::sync_to_sheet`] projects the grades
+/// onto a Google sheet. Students and assignments are addressed by their database ids.
+pub struct SqliteGradeStore {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteGradeStore {
+    /// Opens (or creates) the database and brings its schema up to date.
+    pub fn open(path: &Path) -> Result<Self> {
+        let connection = Connection::open(path)
+            .map_err(|e| Error::config(format!("Can't open the database {}: {}", path.display(), e)))?;
+        // Parallel jobs wait for each other instead of failing
+        connection.busy_timeout(Duration::from_secs(30))?;
+        connection.pragma_update(None, "foreign_keys", true)?;
+        let mut store = SqliteGradeStore {
+            connection: Mutex::new(connection),
+        };
+        store.migrate()?;
+        Ok(store)
+    }
+
+    fn migrate(&mut self) -> Result<()> {
+        let connection = self.connection.get_mut().unwrap_or_else(|e| e.into_inner());
+        let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let version: i64 = transaction.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        let version = version as usize;
+        if version > MIGRATIONS.len() {
+            return Err(Error::config(format!(
+                "The database has schema version {}, this version of the action supports up to {}",
+                version,
+                MIGRATIONS.len()
+            )));
+        }
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            transaction.execute_batch(migration)?;
+            transaction.pragma_update(None, "user_version", (index + 1) as i64)?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Writes the grades of all students into the sheet, see [`StudentManager::write_grades`].
+    pub async fn sync_to_sheet(&self, sheet: &StudentManager<'_>) -> Result<usize> {
+        let grades = self.all_grades()?;
+        sheet.write_grades(&grades).await
+    }
+
+    // (github_id, assignment name, value) of every grade
+    fn all_grades(&self) -> Result<Vec<(String, String, Value)>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT students.github_id, assignments.name, grades.value
+             FROM grades
+             JOIN students ON students.id = grades.student_id
+             JOIN assignments ON assignments.id = grades.assignment_id
+             ORDER BY students.id, assignments.id",
+        )?;
+        let rows = statement.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+        })?;
+
+        let mut grades = Vec::new();
+        for row in rows {
+            let (github_id, assignment_name, value) = row?;
+            grades.push((github_id, assignment_name, serde_json::from_str(&value)?));
+        }
+        Ok(grades)
+    }
+}
+
+fn student_id(connection: &Connection, github_id: &str) -> Result<usize> {
+    connection.execute(
+        "INSERT INTO students (github_id) VALUES (?1) ON CONFLICT (github_id) DO NOTHING",
+        params![github_id],
+    )?;
+    let id: i64 = connection.query_row(
+        "SELECT id FROM students WHERE github_id = ?1",
+        params![github_id],
+        |row| row.get(0),
+    )?;
+    Ok(id as usize)
+}
+
+fn assignment_id(connection: &Connection, assignment_name: &str) -> Result<usize> {
+    connection.execute(
+        "INSERT INTO assignments (name) VALUES (?1) ON CONFLICT (name) DO NOTHING",
+        params![assignment_name],
+    )?;
+    let id: i64 = connection.query_row(
+        "SELECT id FROM assignments WHERE name = ?1",
+        params![assignment_name],
+        |row| row.get(0),
+    )?;
+    Ok(id as usize)
+}
+
+fn set_grade(connection: &Connection, student: usize, assignment: usize, value: &Value) -> Result<()> {
+    connection.execute(
+        "INSERT INTO grades (student_id, assignment_id, value) VALUES (?1, ?2, ?3)
+         ON CONFLICT (student_id, assignment_id)
+         DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+        params![student as i64, assignment as i64, value.to_string()],
+    )?;
+    Ok(())
+}
+
+#[async_trait]
+impl GradeSink for SqliteGradeStore {
+    async fn find_or_create_student(&self, github_id: &str) -> Result<usize> {
+        student_id(&self.connection(), github_id)
+    }
+
+    async fn find_or_create_assignment(&self, assignment_name: &str) -> Result<usize> {
+        assignment_id(&self.connection(), assignment_name)
+    }
+
+    async fn read_cell(&self, student: usize, assignment: usize) -> Result<Option<String>> {
+        let value: Option<String> = self
+            .connection()
+            .query_row(
+                "SELECT value FROM grades WHERE student_id = ?1 AND assignment_id = ?2",
+                params![student as i64, assignment as i64],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match value {
+            Some(value) => Ok(Some(cell_text(&serde_json::from_str(&value)?))),
+            None => Ok(None),
+        }
+    }
+
+    async fn write_cell(&self, student: usize, assignment: usize, value: Value) -> Result<()> {
+        set_grade(&self.connection(), student, assignment, &value)
+    }
+
+    async fn write_cells(&self, cells: &[(usize, usize, Value)]) -> Result<()> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        for (student, assignment, value) in cells {
+            set_grade(&transaction, *student, *assignment, value)?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    // Stores the submission with all its test results and updates the grades in one transaction
+    async fn record_results(
+        &self,
+        github_id: &str,
+        results: &TestResults,
+        status_values: &StatusValues,
+    ) -> Result<()> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let student = student_id(&transaction, github_id)?;
+        transaction.execute(
+            "INSERT INTO submissions (student_id, status, max_score, repository, commit_sha)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                student as i64,
+                results.status.as_str(),
+                results.max_score,
+                std::env::var("GITHUB_REPOSITORY").ok(),
+                std::env::var("GITHUB_SHA").ok(),
+            ],
+        )?;
+        let submission = transaction.last_insert_rowid();
+
+        for test in &results.tests {
+            let assignment = assignment_id(&transaction, &test.name)?;
+            transaction.execute(
+                "INSERT INTO test_results (submission_id, assignment_id, status, score, points, duration)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    submission,
+                    assignment as i64,
+                    test.status.as_str(),
+                    test.score,
+                    test.points,
+                    test.duration.map(|duration| duration as i64),
+                ],
+            )?;
+            set_grade(&transaction, student, assignment, &status_values.cell_value(test))?;
+        }
+
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn results(tests: &[(&str, &str)]) -> TestResults {
+        let tests: Vec<Value> = tests
+            .iter()
+            .map(|(name, status)| {
+                json!({"name": name, "status": status, "score": null, "points": null, "test_code": null,
+                       "filename": null, "line_no": null, "duration": null})
+            })
+            .collect();
+        serde_json::from_value(json!({"version": 1, "status": "fail", "max_score": null, "tests": tests})).unwrap()
+    }
+
+    fn count(store: &SqliteGradeStore, table: &str) -> i64 {
+        store
+            .connection()
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn upgrades_the_schema_of_an_older_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("grades.db");
+        {
+            let connection = Connection::open(&path).unwrap();
+            connection.execute_batch(MIGRATIONS[0]).unwrap();
+            connection.pragma_update(None, "user_version", 1).unwrap();
+            connection.execute("INSERT INTO students (github_id) VALUES ('alice')", []).unwrap();
+        }
+
+        let store = SqliteGradeStore::open(&path).unwrap();
+        let version: i64 = store
+            .connection()
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len() as i64);
+        assert_eq!(count(&store, "students"), 1);
+        assert_eq!(count(&store, "grades"), 0);
+    }
+
+    #[test]
+    fn refuses_a_newer_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("grades.db");
+        Connection::open(&path)
+            .unwrap()
+            .pragma_update(None, "user_version", MIGRATIONS.len() as i64 + 1)
+            .unwrap();
+
+        let err = SqliteGradeStore::open(&path).err().unwrap();
+        assert_eq!(err.kind(), crate::error::ErrorKind::Config);
+    }
+
+    #[tokio::test]
+    async fn reads_back_the_written_grades() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteGradeStore::open(&dir.path().join("grades.db")).unwrap();
+
+        let student = store.find_or_create_student("alice").await.unwrap();
+        let task01 = store.find_or_create_assignment("task01").await.unwrap();
+        let task02 = store.find_or_create_assignment("task02").await.unwrap();
+        assert_eq!(store.find_or_create_student("alice").await.unwrap(), student);
+        assert_eq!(store.read_cell(student, task01).await.unwrap(), None);
+
+        store.write_cell(student, task01, json!(2.5)).await.unwrap();
+        store.write_cells(&[(student, task02, json!("TL"))]).await.unwrap();
+        assert_eq!(store.read_cell(student, task01).await.unwrap().as_deref(), Some("2.5"));
+        assert_eq!(store.read_cell(student, task02).await.unwrap().as_deref(), Some("TL"));
+    }
+
+    #[tokio::test]
+    async fn records_a_submission_in_one_transaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteGradeStore::open(&dir.path().join("grades.db")).unwrap();
+
+        store
+            .record_results("alice", &results(&[("task01", "pass"), ("task02", "fail")]), &StatusValues::new(false))
+            .await
+            .unwrap();
+        store
+            .record_results("bob", &results(&[("task01", "fail")]), &StatusValues::new(false))
+            .await
+            .unwrap();
+        assert_eq!(count(&store, "submissions"), 2);
+        assert_eq!(count(&store, "test_results"), 3);
+
+        // The grades keep their JSON type, in the order of the students and assignments
+        assert_eq!(
+            store.all_grades().unwrap(),
+            vec![
+                ("alice".to_string(), "task01".to_string(), json!(1)),
+                ("alice".to_string(), "task02".to_string(), json!(0)),
+                ("bob".to_string(), "task01".to_string(), json!(0)),
+            ]
+        );
+
+        // A test result the database rejects rolls back the whole submission
+        store
+            .connection()
+            .execute_batch(
+                "CREATE TRIGGER no_errors BEFORE INSERT ON test_results WHEN NEW.status = 'error'
+                 BEGIN SELECT RAISE(ABORT, 'error results are not accepted'); END;",
+            )
+            .unwrap();
+        let err = store
+            .record_results("carol", &results(&[("task03", "pass"), ("task04", "error")]), &StatusValues::new(false))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), crate::error::ErrorKind::Api);
+        assert_eq!(count(&store, "students"), 2);
+        assert_eq!(count(&store, "assignments"), 2);
+        assert_eq!(count(&store, "submissions"), 2);
+        assert_eq!(count(&store, "grades"), 3);
+    }
+}
@@ -0,0 +1,271 @@
+use crate::config::inputs::{optional_input, required_input};
+use crate::error::{Error, ErrorKind, Result};
+use crate::http::client::ApiClient;
+use crate::utils::json_parser::{TestResults, TestStatus};
+use crate::utils::status_values::StatusValues;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use serde::Serialize;
+use serde_json::Value;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Header with the HMAC-SHA256 of the timestamp and the body, `sha256=<hex>`, see [`signature`].
+pub const SIGNATURE_HEADER: &str = "X-Classroom-Signature-256";
+
+/// Header with the Unix time of the delivery, part of the signed material.
+pub const TIMESTAMP_HEADER: &str = "X-Classroom-Timestamp";
+
+/// Version of the payload, raised when a field changes its meaning or is removed.
+pub const PAYLOAD_VERSION: u8 = 1;
+
+/// Endpoint the submissions are posted to.
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Key of the signature, requests are not signed without it.
+    pub secret: Option<String>,
+    /// Attempts after a failed delivery.
+    pub retries: u32,
+    /// Name of the assignment in the payload.
+    pub assignment: Option<String>,
+}
+
+impl WebhookConfig {
+    /// Reads `INPUT_WEBHOOK_URL`, `INPUT_WEBHOOK_SECRET`, `INPUT_WEBHOOK_RETRIES` (3 by default)
+    /// and `INPUT_WEBHOOK_ASSIGNMENT`.
+    pub fn from_inputs() -> Result<Self> {
+        let retries = match optional_input("INPUT_WEBHOOK_RETRIES") {
+            Some(retries) => retries.trim().parse().map_err(|_| {
+                Error::config(format!("INPUT_WEBHOOK_RETRIES must be a number, got '{}'", retries))
+            })?,
+            None => 3,
+        };
+        Ok(WebhookConfig {
+            url: required_input("INPUT_WEBHOOK_URL")?.trim().to_string(),
+            secret: optional_input("INPUT_WEBHOOK_SECRET"),
+            retries,
+            assignment: optional_input("INPUT_WEBHOOK_ASSIGNMENT"),
+        })
+    }
+}
+
+/// Payload of a delivery, see the "Webhook" section of the Readme.
+#[derive(Debug, Clone, Serialize)]
+pub struct SubmissionEvent {
+    pub version: u8,
+    pub event: &'static str,
+    /// Same for every attempt of a delivery, lets the receiver drop duplicates.
+    pub delivery_id: String,
+    /// Unix time of the grading.
+    pub timestamp: u64,
+    pub student: String,
+    pub assignment: Option<String>,
+    pub repository: Option<String>,
+    pub commit: Option<String>,
+    pub run_url: Option<String>,
+    pub status: TestStatus,
+    pub score: f64,
+    pub max_score: Option<f64>,
+    pub tests: Vec<TestEvent>,
+}
+
+/// One test of a [`SubmissionEvent`].
+#[derive(Debug, Clone, Serialize)]
+pub struct TestEvent {
+    pub name: String,
+    pub status: TestStatus,
+    pub score: Option<f64>,
+    pub points: Option<f64>,
+    /// Value written into the gradebook for the test.
+    pub value: Value,
+}
+
+impl SubmissionEvent {
+    /// Describes the graded submission, with the run taken from the GitHub Actions variables.
+    pub fn new(
+        github_id: &str,
+        results: &TestResults,
+        status_values: &StatusValues,
+        assignment: Option<&str>,
+    ) -> Self {
+        let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let repository = env("GITHUB_REPOSITORY");
+        let run_url = match (env("GITHUB_SERVER_URL"), &repository, env("GITHUB_RUN_ID")) {
+            (Some(server), Some(repository), Some(run_id)) => {
+                Some(format!("{}/{}/actions/runs/{}", server, repository, run_id))
+            }
+            _ => None,
+        };
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let delivery_id = match (env("GITHUB_RUN_ID"), env("GITHUB_RUN_ATTEMPT")) {
+            (Some(run_id), Some(attempt)) => format!("{}-{}-{}", run_id, attempt, github_id),
+            _ => format!("{:x}-{:x}", timestamp.as_nanos(), std::process::id()),
+        };
+        // GitHub Classroom names the repositories `<assignment>-<student>`
+        let assignment = assignment.map(str::to_string).or_else(|| {
+            let name = repository.as_deref()?.rsplit('/').next()?;
+            name.strip_suffix(&format!("-{}", github_id)).map(str::to_string)
+        });
+
+        // Sum of the scores, or the number of passed tests when no test has a score
+        let scored = results.tests.iter().any(|test| test.score.is_some());
+        let score = if scored {
+            results.tests.iter().filter_map(|test| test.score).sum()
+        } else {
+            results.tests.iter().filter(|test| test.status == TestStatus::Pass).count() as f64
+        };
+        let max_score = results
+            .max_score
+            .or_else(|| (!scored).then_some(results.tests.len() as f64));
+
+        SubmissionEvent {
+            version: PAYLOAD_VERSION,
+            event: "submission",
+            delivery_id,
+            timestamp: timestamp.as_secs(),
+            student: github_id.to_string(),
+            assignment,
+            repository,
+            commit: env("GITHUB_SHA"),
+            run_url,
+            status: results.status,
+            score,
+            max_score,
+            tests: results
+                .tests
+                .iter()
+                .map(|test| TestEvent {
+                    name: test.name.clone(),
+                    status: test.status,
+                    score: test.score,
+                    points: test.points,
+                    value: status_values.cell_value(test),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// `sha256=<hex>` of the HMAC-SHA256 of `<timestamp>.<body>` with the secret.
+///
+/// The timestamp is signed so that receivers can reject old deliveries: a captured
+/// request can't be replayed later with a new timestamp.
+pub fn signature(secret: &str, timestamp: u64, body: &[u8]) -> Result<String> {
+    let mut message = format!("{}.", timestamp).into_bytes();
+    message.extend_from_slice(body);
+    let hex: String = hmac_sha256(secret, &message)?
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    Ok(format!("sha256={}", hex))
+}
+
+fn hmac_sha256(secret: &str, message: &[u8]) -> Result<Vec<u8>> {
+    let sign = || -> std::result::Result<Vec<u8>, openssl::error::ErrorStack> {
+        let key = PKey::hmac(secret.as_bytes())?;
+        let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+        signer.update(message)?;
+        signer.sign_to_vec()
+    };
+    sign().map_err(|e| Error::config(format!("Can't sign the webhook payload: {}", e)))
+}
+
+/// Posts every graded submission to a URL, next to the gradebook backend.
+///
+/// The body is a [`SubmissionEvent`], signed in [`SIGNATURE_HEADER`] when a secret is set.
+/// Network errors, rate limits and server errors are retried with a growing delay,
+/// other client errors are not.
+pub struct WebhookSink {
+    client: ApiClient,
+    config: WebhookConfig,
+}
+
+impl WebhookSink {
+    pub fn new(client: ApiClient, config: WebhookConfig) -> Self {
+        WebhookSink { client, config }
+    }
+
+    pub async fn record_results(
+        &self,
+        github_id: &str,
+        results: &TestResults,
+        status_values: &StatusValues,
+    ) -> Result<()> {
+        let event = SubmissionEvent::new(github_id, results, status_values, self.config.assignment.as_deref());
+        self.deliver(&event).await
+    }
+
+    pub async fn deliver(&self, event: &SubmissionEvent) -> Result<()> {
+        // Request body, signed as it is sent
+        let body = serde_json::to_vec(event)?;
+        let signature = match &self.config.secret {
+            Some(secret) => Some(signature(secret, event.timestamp, &body)?),
+            None => None,
+        };
+
+        let mut attempt = 0;
+        loop {
+            let error = match self.post(&body, signature.as_deref(), event).await {
+                Ok(()) => return Ok(()),
+                Err(error) => error,
+            };
+            let retryable = match error.status() {
+                Some(status) => status == 429 || status >= 500,
+                None => error.kind() == ErrorKind::Network,
+            };
+            if !retryable || attempt >= self.config.retries {
+                return Err(error.context(format!("Can't deliver the webhook to {}", self.config.url)));
+            }
+
+            eprintln!("Webhook delivery failed ({}), retrying", error);
+            tokio::time::sleep(Duration::from_millis(500 << attempt.min(6))).await;
+            attempt += 1;
+        }
+    }
+
+    async fn post(&self, body: &[u8], signature: Option<&str>, event: &SubmissionEvent) -> Result<()> {
+        let mut request = self
+            .client
+            .http
+            .post(&self.config.url)
+            .header("Content-Type", "application/json")
+            .header("X-Classroom-Event", "submission")
+            .header("X-Classroom-Delivery", &event.delivery_id)
+            .header(TIMESTAMP_HEADER, event.timestamp.to_string())
+            .body(body.to_vec());
+        if let Some(signature) = signature {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+
+        // Sending request
+        let resp = request.send().await?;
+
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            Err(Error::from_response(resp).await)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hmac_matches_rfc_4231() {
+        // Test case 2
+        let digest = hmac_sha256("Jefe", b"what do ya want for nothing?").unwrap();
+        let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+        assert_eq!(hex, "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    }
+
+    #[test]
+    fn signs_the_timestamp_with_the_body() {
+        // printf '1760000000.{}' | openssl dgst -sha256 -hmac Jefe
+        let signed = signature("Jefe", 1760000000, b"{}").unwrap();
+        assert_eq!(signed, "sha256=a6f426b8a8f87eb9397f19043a1678909de4623abe5ace4bcf70ce0e5ea39bec");
+        assert_ne!(signature("Jefe", 1760000001, b"{}").unwrap(), signed);
+    }
+}
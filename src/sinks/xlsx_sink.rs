@@ -0,0 +1,99 @@
+use crate::data_processing::table::get_cell;
+use crate::data_processing::utils::find_column_by_header;
+use crate::error::{Error, Result};
+use crate::sinks::local_file::{io_error, replace_file};
+use crate::sinks::local_table::{LocalTableSink, TableFormat};
+use std::path::{Path, PathBuf};
+use umya_spreadsheet::{Workbook, Worksheet};
+
+/// Gradebook kept in one sheet of an Excel workbook, see [`LocalTableSink`].
+///
+/// Only the changed cells of the sheet are written, the other sheets, the formatting
+/// and the formulas of the workbook are kept.
+pub type XlsxGradeSink = LocalTableSink<XlsxFormat>;
+
+impl XlsxGradeSink {
+    /// The workbook and the sheet are created on the first write if they do not exist.
+    pub fn new(path: impl Into<PathBuf>, sheet_name: &str) -> Self {
+        LocalTableSink::with_format(
+            path,
+            XlsxFormat {
+                sheet_name: sheet_name.to_string(),
+            },
+        )
+    }
+}
+
+/// Values of one sheet of the workbook.
+pub struct XlsxFormat {
+    sheet_name: String,
+}
+
+impl XlsxFormat {
+    fn read_book(&self, path: &Path) -> Result<Workbook> {
+        if !path.exists() {
+            let mut book = umya_spreadsheet::new_file();
+            // A new workbook has only "Sheet1", name it after the gradebook sheet
+            book.set_sheet_name(0, self.sheet_name.as_str())
+                .map_err(|e| Error::config(format!("Invalid sheet name '{}': {}", self.sheet_name, e)))?;
+            return Ok(book);
+        }
+        umya_spreadsheet::reader::xlsx::read(path)
+            .map_err(|e| Error::parse(format!("Can't read the workbook {}: {}", path.display(), e)))
+    }
+
+    fn sheet_mut<'b>(&self, book: &'b mut Workbook) -> Result<&'b mut Worksheet> {
+        if book.sheet_by_name(&self.sheet_name).is_err() {
+            book.new_sheet(self.sheet_name.as_str())
+                .map_err(|e| Error::config(format!("Invalid sheet name '{}': {}", self.sheet_name, e)))?;
+        }
+        book.sheet_by_name_mut(&self.sheet_name)
+            .map_err(|e| Error::not_found(format!("Can't find the sheet '{}': {}", self.sheet_name, e)))
+    }
+}
+
+impl TableFormat for XlsxFormat {
+    fn load(&self, path: &Path) -> Result<Vec<Vec<String>>> {
+        let mut book = self.read_book(path)?;
+        Ok(read_table(self.sheet_mut(&mut book)?))
+    }
+
+    // The workbook is read again and only the cells that differ from `before` are set
+    fn save(&self, path: &Path, before: &[Vec<String>], table: &[Vec<String>]) -> Result<()> {
+        let mut book = self.read_book(path)?;
+        let sheet = self.sheet_mut(&mut book)?;
+
+        let github_id_col = find_column_by_header(table, "github_id");
+        for (row_idx, row) in table.iter().enumerate() {
+            for (col_idx, value) in row.iter().enumerate() {
+                if get_cell(before, row_idx, col_idx).unwrap_or_default() != *value {
+                    // Coordinates of umya are (column, row), both starting at 1
+                    let cell = sheet.cell_mut((col_idx as u32 + 1, row_idx as u32 + 1));
+                    // Only grades become numbers, headers, github ids and status values
+                    // like "TRUE" are written as they are
+                    let is_grade = row_idx > 0 && Some(col_idx) != github_id_col;
+                    match value.parse::<f64>() {
+                        Ok(number) if is_grade && number.is_finite() => cell.set_value_number(number),
+                        _ => cell.set_value_string(value.as_str()),
+                    };
+                }
+            }
+        }
+
+        replace_file(path, |tmp_path| {
+            umya_spreadsheet::writer::xlsx::write(&book, tmp_path).map_err(|e| io_error("write", path, e))
+        })
+    }
+
+    fn table_name(&self, path: &Path) -> String {
+        format!("{} ({})", self.sheet_name, path.display())
+    }
+}
+
+// Values of the sheet as text, the same way the Sheets API returns them
+fn read_table(sheet: &Worksheet) -> Vec<Vec<String>> {
+    let (max_col, max_row) = sheet.highest_column_and_row();
+    (1..=max_row)
+        .map(|row| (1..=max_col).map(|col| sheet.value((col, row))).collect())
+        .collect()
+}
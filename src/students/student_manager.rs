@@ -1,57 +1,76 @@
 use crate::data_processing::utils::{
-    find_first_empty_row, find_first_empty_column, find_column_by_header,
+    coords_to_cell_address, find_column_by_header, find_first_empty_column, find_first_empty_row,
+    sheet_range,
 };
-use crate::google_sheets::api::{read_from_sheet, write_to_cell};
-use reqwest::Client;
-
+use crate::data_processing::table;
+use crate::error::{Error, Result};
+use crate::google_sheets::api::{read_from_sheet, write_to_cell, write_to_cells};
+use crate::google_sheets::token_provider::TokenProvider;
+use crate::http::client::ApiClient;
+use crate::sinks::grade_sink::GradeSink;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Reads and writes the results of students in a gradebook sheet.
+///
+/// The sheet must have a `github_id` column, every other column is an assignment.
+/// This is the Google Sheets [`GradeSink`].
 pub struct StudentManager<'a> {
-    pub client: &'a Client,
-    pub access_token: &'a str,
+    pub client: &'a ApiClient,
+    pub tokens: &'a dyn TokenProvider,
     pub spreadsheet_id: &'a str,
     pub sheet_name: &'a str,
 }
 
 impl<'a> StudentManager<'a> {
+    /// Creates a manager for the sheet `sheet_name` of the spreadsheet `spreadsheet_id`.
     pub fn new(
-        client: &'a Client,
-        access_token: &'a str,
+        client: &'a ApiClient,
+        tokens: &'a dyn TokenProvider,
         spreadsheet_id: &'a str,
         sheet_name: &'a str,
     ) -> Self {
         StudentManager {
             client,
-            access_token,
+            tokens,
             spreadsheet_id,
             sheet_name,
         }
     }
 
-    pub async fn get_or_create_student_row(
-        &self,
-        github_id: &str,
-    ) -> Result<usize, Box<dyn std::error::Error>> {
-        // Read the data from the table
-        let read_range = format!("{}!A1:ZZ1000", self.sheet_name);
+    /// Reads the values of the whole sheet.
+    pub async fn read_table(&self) -> Result<Vec<Vec<String>>> {
+        let read_range = sheet_range(self.sheet_name, "A1:ZZ1000");
         let data = read_from_sheet(
             self.client,
-            self.access_token,
+            self.tokens,
             self.spreadsheet_id,
             &read_range,
         )
             .await?;
 
         // Parse the data into a table structure
-        let table = crate::data_processing::parser::parse_sheet_data(&data)?;
+        crate::data_processing::parser::parse_sheet_data(&data)
+    }
+
+    /// Returns the row of the student, appending a new row if the student is not in the sheet.
+    pub async fn get_or_create_student_row(
+        &self,
+        github_id: &str,
+    ) -> Result<usize> {
+        // Read the data from the table
+        let table = self.read_table().await?;
 
         // Find the column index for 'github_id'
         let github_id_col = find_column_by_header(&table, "github_id")
-            .ok_or("Столбец 'github_id' не найден")?;
+            .ok_or_else(|| Error::not_found(format!("Column 'github_id' not found in {}", self.sheet_name)))?;
 
         // Search for the student by GitHub ID
-        for (row_idx, row) in table.iter().enumerate().skip(1) { // Пропускаем заголовок
+        for (row_idx, row) in table.iter().enumerate().skip(1) { // Skip the header
             if let Some(cell_value) = row.get(github_id_col) {
                 if cell_value == github_id {
-                    // Студент найден, возвращаем индекс строки
+                    // Student found, return the row index
                     return Ok(row_idx);
                 }
             }
@@ -63,8 +82,9 @@ impl<'a> StudentManager<'a> {
         // Write the GitHub ID to the 'github_id' column in the new row
         write_to_cell(
             self.client,
-            self.access_token,
+            self.tokens,
             self.spreadsheet_id,
+            self.sheet_name,
             new_row_idx,
             github_id_col,
             serde_json::json!(github_id),
@@ -74,20 +94,39 @@ impl<'a> StudentManager<'a> {
         Ok(new_row_idx)
     }
 
-    pub async fn update_assignment_result(
+    /// Reads the result of the student for the assignment, if both are present in the sheet.
+    pub async fn read_assignment_result(
         &self,
         github_id: &str,
         assignment_name: &str,
-        result: i32,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        // Find or create a student row
-        let student_row = self.get_or_create_student_row(github_id).await?;
+    ) -> Result<Option<String>> {
+        // Read the data from the table
+        let table = self.read_table().await?;
+
+        let github_id_col = match find_column_by_header(&table, "github_id") {
+            Some(col_idx) => col_idx,
+            None => return Ok(None),
+        };
+        let assignment_col = match find_column_by_header(&table, assignment_name) {
+            Some(col_idx) => col_idx,
+            None => return Ok(None),
+        };
 
+        let result = table
+            .iter()
+            .skip(1)
+            .find(|row| row.get(github_id_col).is_some_and(|cell| cell == github_id))
+            .and_then(|row| row.get(assignment_col).cloned());
+        Ok(result)
+    }
+
+    /// Returns the column of the assignment, adding it to the header if it is not there yet.
+    pub async fn get_or_create_assignment_column(&self, assignment_name: &str) -> Result<usize> {
         // Read the data from the table (only the first row - headers)
-        let header_range = format!("{}!A1:Z1", self.sheet_name);
+        let header_range = sheet_range(self.sheet_name, "A1:Z1");
         let data = read_from_sheet(
             self.client,
-            self.access_token,
+            self.tokens,
             self.spreadsheet_id,
             &header_range,
         )
@@ -96,55 +135,85 @@ impl<'a> StudentManager<'a> {
         // Parse the data into a table structure
         let table = crate::data_processing::parser::parse_sheet_data(&data)?;
 
-        let header_row = if !table.is_empty() {
-            table[0].clone()
-        } else {
+        if table.is_empty() {
             // If the table is empty, create a header with 'github_id'
             write_to_cell(
                 self.client,
-                self.access_token,
+                self.tokens,
                 self.spreadsheet_id,
+                self.sheet_name,
                 0,
                 0,
                 serde_json::json!("github_id"),
             )
                 .await?;
-            vec!["github_id".to_string()]
-        };
+        }
 
         // Find the assignment column index
-        let assignment_col = find_column_by_header(&table, assignment_name);
-
-        let assignment_col = match assignment_col {
-            Some(col_idx) => col_idx,
-            None => {
-                // Column not found, create a new column in the first empty column
-                let new_col_idx = find_first_empty_column(&table);
-                // Write the assignment name to the header
-                write_to_cell(
-                    self.client,
-                    self.access_token,
-                    self.spreadsheet_id,
-                    0,
-                    new_col_idx,
-                    serde_json::json!(assignment_name),
-                )
-                    .await?;
-                new_col_idx
-            }
-        };
+        if let Some(col_idx) = find_column_by_header(&table, assignment_name) {
+            return Ok(col_idx);
+        }
 
-        // Write the result to the cell at the intersection of the student row and the assignment column
+        // Column not found, create a new column in the first empty column
+        let new_col_idx = find_first_empty_column(&table);
+        // Write the assignment name to the header
         write_to_cell(
             self.client,
-            self.access_token,
+            self.tokens,
             self.spreadsheet_id,
-            student_row,
-            assignment_col,
-            serde_json::json!(result),
+            self.sheet_name,
+            0,
+            new_col_idx,
+            serde_json::json!(assignment_name),
         )
             .await?;
+        Ok(new_col_idx)
+    }
+
+    /// Writes the grades of many students, given as (github_id, assignment, value), adding missing rows
+    /// and columns. Cells that already hold the value are not written, the others are written in one
+    /// request. Returns the number of written cells.
+    pub async fn write_grades(&self, grades: &[(String, String, Value)]) -> Result<usize> {
+        // Lay the grades out on a copy of the sheet and write only the difference
+        let before = self.read_table().await?;
+        let mut table = before.clone();
+        let mut values = HashMap::new();
+        for (github_id, assignment_name, value) in grades {
+            let row = table::get_or_create_student_row(&mut table, github_id, self.sheet_name)?;
+            let col = table::get_or_create_assignment_column(&mut table, assignment_name);
+            table::set_cell(&mut table, row, col, table::cell_text(value));
+            values.insert((row, col), value.clone());
+        }
+
+        let cells = table::changed_cells(&before, &table, values);
+        write_to_cells(self.client, self.tokens, self.spreadsheet_id, self.sheet_name, &cells).await?;
+        Ok(cells.len())
+    }
+}
+
+#[async_trait]
+impl GradeSink for StudentManager<'_> {
+    async fn find_or_create_student(&self, github_id: &str) -> Result<usize> {
+        self.get_or_create_student_row(github_id).await
+    }
+
+    async fn find_or_create_assignment(&self, assignment_name: &str) -> Result<usize> {
+        self.get_or_create_assignment_column(assignment_name).await
+    }
+
+    async fn read_cell(&self, student: usize, assignment: usize) -> Result<Option<String>> {
+        let range = sheet_range(self.sheet_name, &coords_to_cell_address(student, assignment));
+        let data = read_from_sheet(self.client, self.tokens, self.spreadsheet_id, &range).await?;
+        let table = crate::data_processing::parser::parse_sheet_data(&data)?;
+        Ok(table.first().and_then(|row| row.first()).cloned())
+    }
+
+    async fn write_cell(&self, student: usize, assignment: usize, value: Value) -> Result<()> {
+        write_to_cell(self.client, self.tokens, self.spreadsheet_id, self.sheet_name, student, assignment, value).await
+    }
 
-        Ok(())
+    // The Sheets API writes all cells in one request
+    async fn write_cells(&self, cells: &[(usize, usize, Value)]) -> Result<()> {
+        write_to_cells(self.client, self.tokens, self.spreadsheet_id, self.sheet_name, cells).await
     }
 }
@@ -0,0 +1,161 @@
+use crate::utils::json_parser::{TestResults, TestStatus};
+use serde::Deserialize;
+use crate::error::{Error, Result};
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Deserialize)]
+pub struct AutogradingTest {
+    pub name: String,
+    pub points: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AutogradingConfig {
+    pub tests: Vec<AutogradingTest>,
+}
+
+impl AutogradingConfig {
+    pub fn points_for(&self, test_name: &str) -> Option<f64> {
+        self.tests
+            .iter()
+            .find(|test| test.name == test_name)
+            .and_then(|test| test.points)
+    }
+}
+
+/// Reads `.github/classroom/autograding.json` (or any file of the same format).
+pub fn load_autograding_config(path: &Path) -> Result<AutogradingConfig> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| Error::config(format!("Can't read {}: {}", path.display(), e)))?;
+    let config: AutogradingConfig = serde_json::from_str(&content)
+        .map_err(|e| Error::parse(format!("Invalid {}: {}", path.display(), e)))?;
+    Ok(config)
+}
+
+/// Sets the points of tests whose runner did not report a score.
+pub fn apply_weights(results: &mut TestResults, config: &AutogradingConfig) {
+    for test in results.tests.iter_mut() {
+        // Runners without configured points report nothing, or zero and no points for a passed test
+        let score_omitted = match test.score {
+            None => true,
+            Some(score) => score == 0.0 && test.points.is_none() && test.status == TestStatus::Pass,
+        };
+        if !score_omitted {
+            continue;
+        }
+
+        if let Some(points) = config.points_for(&test.name) {
+            test.points = Some(points);
+            test.score = Some(if test.status == TestStatus::Pass { points } else { 0.0 });
+        }
+    }
+
+    // Fill the max score if no runner reported it
+    if results.max_score.unwrap_or(0.0) == 0.0 {
+        let total: f64 = config.tests.iter().filter_map(|test| test.points).sum();
+        if total > 0.0 {
+            results.max_score = Some(total);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use serde_json::json;
+
+    fn config() -> AutogradingConfig {
+        serde_json::from_value(json!({"tests": [
+            {"name": "task01", "points": 2.0},
+            {"name": "task02", "points": 3.0},
+            {"name": "task03"}
+        ]}))
+        .unwrap()
+    }
+
+    fn results(tests: serde_json::Value) -> TestResults {
+        let tests: Vec<serde_json::Value> = tests
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|test| {
+                let mut full = json!({"score": null, "points": null, "test_code": null, "filename": null,
+                                      "line_no": null, "duration": null});
+                full.as_object_mut().unwrap().extend(test.as_object().unwrap().clone());
+                full
+            })
+            .collect();
+        serde_json::from_value(json!({"version": 1, "status": "fail", "max_score": null, "tests": tests})).unwrap()
+    }
+
+    fn scores(results: &TestResults) -> Vec<(Option<f64>, Option<f64>)> {
+        results.tests.iter().map(|test| (test.score, test.points)).collect()
+    }
+
+    #[test]
+    fn fills_the_scores_the_runner_omitted() {
+        let mut results = results(json!([
+            {"name": "task01", "status": "pass"},
+            {"name": "task02", "status": "fail"},
+            {"name": "task03", "status": "pass", "score": 0.0}
+        ]));
+        apply_weights(&mut results, &config());
+        // task03 has no configured points and keeps what the runner reported
+        assert_eq!(scores(&results), [(Some(2.0), Some(2.0)), (Some(0.0), Some(3.0)), (Some(0.0), None)]);
+    }
+
+    #[test]
+    fn keeps_the_scores_the_runner_reported() {
+        let mut results = results(json!([
+            {"name": "task01", "status": "pass", "score": 0.0, "points": 1.0},
+            {"name": "task02", "status": "pass", "score": 1.5}
+        ]));
+        apply_weights(&mut results, &config());
+        assert_eq!(scores(&results), [(Some(0.0), Some(1.0)), (Some(1.5), None)]);
+    }
+
+    #[test]
+    fn a_zero_without_points_is_an_omitted_score() {
+        let mut results = results(json!([{"name": "task01", "status": "pass", "score": 0.0}]));
+        apply_weights(&mut results, &config());
+        assert_eq!(scores(&results), [(Some(2.0), Some(2.0))]);
+    }
+
+    #[test]
+    fn ignores_tests_missing_from_the_config() {
+        let mut results = results(json!([{"name": "task99", "status": "pass"}]));
+        apply_weights(&mut results, &config());
+        assert_eq!(scores(&results), [(None, None)]);
+    }
+
+    #[test]
+    fn fills_the_max_score_only_when_no_runner_reported_it() {
+        let mut results = results(json!([]));
+        apply_weights(&mut results, &config());
+        assert_eq!(results.max_score, Some(5.0));
+
+        results.max_score = Some(10.0);
+        apply_weights(&mut results, &config());
+        assert_eq!(results.max_score, Some(10.0));
+    }
+
+    #[test]
+    fn loads_the_classroom_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("autograding.json");
+        fs::write(
+            &path,
+            r#"{"tests": [{"name": "task01", "setup": "", "run": "make test", "input": "", "output": "",
+                           "comparison": "included", "timeout": 10, "points": 2}]}"#,
+        )
+        .unwrap();
+        assert_eq!(load_autograding_config(&path).unwrap().points_for("task01"), Some(2.0));
+
+        fs::write(&path, r#"{"tests": "#).unwrap();
+        assert_eq!(load_autograding_config(&path).unwrap_err().kind(), ErrorKind::Parse);
+        let missing = dir.path().join("missing.json");
+        assert_eq!(load_autograding_config(&missing).unwrap_err().kind(), ErrorKind::Config);
+    }
+}
@@ -1,12 +1,65 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use base64::decode;
-use std::error::Error;
+use crate::error::{Error, Result};
+
+/// Latest version of the results format produced by the classroom graders.
+pub const SUPPORTED_VERSION: u8 = 1;
+
+/// Outcome of a single test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", from = "String")]
+pub enum TestStatus {
+    Pass,
+    Fail,
+    Error,
+    Skip,
+    Timeout,
+    Unknown,
+}
+
+impl TestStatus {
+    pub const ALL: [TestStatus; 6] = [
+        TestStatus::Pass,
+        TestStatus::Fail,
+        TestStatus::Error,
+        TestStatus::Skip,
+        TestStatus::Timeout,
+        TestStatus::Unknown,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TestStatus::Pass => "pass",
+            TestStatus::Fail => "fail",
+            TestStatus::Error => "error",
+            TestStatus::Skip => "skip",
+            TestStatus::Timeout => "timeout",
+            TestStatus::Unknown => "unknown",
+        }
+    }
+}
+
+impl From<String> for TestStatus {
+    fn from(status: String) -> Self {
+        // Graders are not consistent in naming, so accept the common spellings
+        match status.to_lowercase().as_str() {
+            "pass" | "passed" | "success" => TestStatus::Pass,
+            "fail" | "failed" | "failure" => TestStatus::Fail,
+            "error" | "errored" => TestStatus::Error,
+            "skip" | "skipped" => TestStatus::Skip,
+            "timeout" | "timed_out" | "timedout" => TestStatus::Timeout,
+            _ => TestStatus::Unknown,
+        }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TestResult {
     pub name: String,
-    pub status: String,
+    pub status: TestStatus,
     pub score: Option<f64>,
+    pub points: Option<f64>,
     pub test_code: Option<String>,
     pub filename: Option<String>,
     pub line_no: Option<u32>,
@@ -16,17 +69,113 @@ pub struct TestResult {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TestResults {
     pub version: u8,
-    pub status: String,
+    pub status: TestStatus,
     pub max_score: Option<f64>,
     pub tests: Vec<TestResult>,
 }
 
-pub fn parse_results(base64_encoded: &str) -> Result<TestResults, Box<dyn Error>> {
+/// Decodes the base64 output of a grader and checks its format version.
+pub fn parse_results(base64_encoded: &str) -> Result<TestResults> {
     // Base64 decoding
     let decoded_bytes = decode(base64_encoded)?;
     let decoded_str = String::from_utf8(decoded_bytes)?;
 
     // JSON parsing
-    let test_results: TestResults = serde_json::from_str(&decoded_str)?;
+    let raw: Value = serde_json::from_str(&decoded_str)?;
+    let test_results: TestResults = serde_json::from_value(upgrade_results(raw)?)?;
     Ok(test_results)
 }
+
+fn upgrade_results(mut raw: Value) -> Result<Value> {
+    if !raw.is_object() {
+        return Err(Error::parse("Test results must be a JSON object"));
+    }
+    match raw.get("version") {
+        // Results written before the format was versioned have the same layout as version 1
+        None | Some(Value::Null) => {
+            raw["version"] = serde_json::json!(SUPPORTED_VERSION);
+            Ok(raw)
+        }
+        Some(version) if version.as_u64() == Some(SUPPORTED_VERSION as u64) => Ok(raw),
+        Some(version) => Err(Error::parse(format!(
+            "Unsupported test results version {} (supported version is {})",
+            version, SUPPORTED_VERSION
+        ))),
+    }
+}
+
+/// Combines the results of several runners into one submission.
+pub fn merge_results(results: Vec<TestResults>) -> Result<TestResults> {
+    let mut iter = results.into_iter();
+    let mut merged = iter.next().ok_or_else(|| Error::config("No test results to merge"))?;
+
+    for result in iter {
+        // Sum the max scores, keeping None only if no runner reported one
+        merged.max_score = match (merged.max_score, result.max_score) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+        // The submission passes only if every runner passed
+        if result.status != TestStatus::Pass {
+            merged.status = result.status;
+        }
+        merged.tests.extend(result.tests);
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::encode;
+
+    #[test]
+    fn rejects_results_that_are_not_an_object() {
+        for payload in ["[]", "\"passed\"", "5", "null"] {
+            let err = parse_results(&encode(payload)).unwrap_err();
+            assert_eq!(err.kind(), crate::error::ErrorKind::Parse, "{}", payload);
+        }
+    }
+
+    #[test]
+    fn reads_unversioned_results_as_version_1() {
+        let results = parse_results(&encode(r#"{"status": "pass", "tests": []}"#)).unwrap();
+        assert_eq!(results.version, SUPPORTED_VERSION);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let err = parse_results(&encode(r#"{"version": 2, "status": "pass", "tests": []}"#)).unwrap_err();
+        assert_eq!(err.kind(), crate::error::ErrorKind::Parse);
+    }
+
+    fn results(status: &str, max_score: Option<f64>, tests: &[&str]) -> TestResults {
+        let tests: Vec<Value> = tests
+            .iter()
+            .map(|name| serde_json::json!({"name": name, "status": status, "score": null, "points": null,
+                                           "test_code": null, "filename": null, "line_no": null, "duration": null}))
+            .collect();
+        serde_json::from_value(serde_json::json!({"version": 1, "status": status, "max_score": max_score, "tests": tests}))
+            .unwrap()
+    }
+
+    #[test]
+    fn merges_the_results_of_several_runners() {
+        let merged = merge_results(vec![
+            results("pass", Some(2.0), &["task01", "task02"]),
+            results("fail", None, &["task03"]),
+            results("pass", Some(1.0), &["task04"]),
+        ])
+        .unwrap();
+
+        assert_eq!(merged.status, TestStatus::Fail);
+        assert_eq!(merged.max_score, Some(3.0));
+        let names: Vec<&str> = merged.tests.iter().map(|test| test.name.as_str()).collect();
+        assert_eq!(names, ["task01", "task02", "task03", "task04"]);
+    }
+
+    #[test]
+    fn merging_no_results_is_an_error() {
+        assert_eq!(merge_results(Vec::new()).unwrap_err().kind(), crate::error::ErrorKind::Config);
+    }
+}
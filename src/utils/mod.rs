@@ -1 +1,3 @@
-pub mod json_parser;
\ No newline at end of file
+pub mod json_parser;
+pub mod autograding_config;
+pub mod status_values;
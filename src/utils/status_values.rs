@@ -0,0 +1,101 @@
+use crate::utils::json_parser::{TestResult, TestStatus};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use crate::error::{Error, Result};
+
+/// Maps the status of a test to the value written into the sheet.
+#[derive(Debug, Clone, Default)]
+pub struct StatusValues {
+    values: HashMap<TestStatus, Value>,
+    use_scores: bool,
+}
+
+impl StatusValues {
+    pub fn new(use_scores: bool) -> Self {
+        StatusValues {
+            values: HashMap::new(),
+            use_scores,
+        }
+    }
+
+    /// Parses a JSON object like `{"error": "ERR", "skip": ""}`.
+    pub fn from_json(json: &str, use_scores: bool) -> Result<Self> {
+        let mut status_values = StatusValues::new(use_scores);
+        if json.trim().is_empty() {
+            return Ok(status_values);
+        }
+
+        let map: Map<String, Value> = serde_json::from_str(json)
+            .map_err(|e| Error::config(format!("Invalid status values: {}", e)))?;
+        for (key, value) in map {
+            let status = TestStatus::ALL
+                .into_iter()
+                .find(|status| status.as_str() == key)
+                .ok_or_else(|| Error::config(format!("Unknown test status '{}' in status values", key)))?;
+            status_values.values.insert(status, value);
+        }
+        Ok(status_values)
+    }
+
+    /// Returns the configured value for the status, otherwise the score or `1`/`0`.
+    pub fn cell_value(&self, test: &TestResult) -> Value {
+        if let Some(value) = self.values.get(&test.status) {
+            return value.clone();
+        }
+
+        match test.score {
+            Some(score) if self.use_scores => serde_json::json!(score),
+            _ if test.status == TestStatus::Pass => serde_json::json!(1),
+            _ => serde_json::json!(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn test(status: TestStatus, score: Option<f64>) -> TestResult {
+        TestResult {
+            name: "task01".to_string(),
+            status,
+            score,
+            points: None,
+            test_code: None,
+            filename: None,
+            line_no: None,
+            duration: None,
+        }
+    }
+
+    #[test]
+    fn writes_1_or_0_by_default() {
+        let status_values = StatusValues::new(false);
+        assert_eq!(status_values.cell_value(&test(TestStatus::Pass, Some(5.0))), json!(1));
+        assert_eq!(status_values.cell_value(&test(TestStatus::Fail, None)), json!(0));
+        assert_eq!(status_values.cell_value(&test(TestStatus::Timeout, None)), json!(0));
+    }
+
+    #[test]
+    fn writes_the_score_when_scores_are_used() {
+        let status_values = StatusValues::new(true);
+        assert_eq!(status_values.cell_value(&test(TestStatus::Fail, Some(2.5))), json!(2.5));
+        // Without a score the status decides
+        assert_eq!(status_values.cell_value(&test(TestStatus::Pass, None)), json!(1));
+    }
+
+    #[test]
+    fn configured_values_replace_the_score() {
+        let status_values = StatusValues::from_json(r#"{"error": "ERR", "skip": ""}"#, true).unwrap();
+        assert_eq!(status_values.cell_value(&test(TestStatus::Error, Some(1.0))), json!("ERR"));
+        assert_eq!(status_values.cell_value(&test(TestStatus::Skip, None)), json!(""));
+        assert_eq!(status_values.cell_value(&test(TestStatus::Pass, Some(3.0))), json!(3.0));
+    }
+
+    #[test]
+    fn rejects_unknown_statuses() {
+        let err = StatusValues::from_json(r#"{"passed": "OK"}"#, false).unwrap_err();
+        assert_eq!(err.kind(), crate::error::ErrorKind::Config);
+    }
+}
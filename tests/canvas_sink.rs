@@ -0,0 +1,68 @@
+mod common;
+
+use github_classroom_spreadsheets_integration::{ApiClient, CanvasConfig, CanvasGradeSink, ErrorKind, GradeSink};
+use serde_json::json;
+use std::collections::HashMap;
+
+fn sink(base_url: String) -> CanvasGradeSink {
+    CanvasGradeSink::new(
+        ApiClient::default(),
+        CanvasConfig {
+            base_url,
+            course_id: "42".to_string(),
+            token: "teacher-token".to_string(),
+            roster: HashMap::from([("octocat".to_string(), "1001".to_string())]),
+            assignments: HashMap::from([("task01".to_string(), "7".to_string())]),
+        },
+    )
+}
+
+#[tokio::test]
+async fn posts_the_grade_to_the_submission() {
+    let (base_url, server) = common::serve(vec![(200, r#"{"id": 1, "grade": "1"}"#)]);
+    let sink = sink(base_url);
+
+    let student = sink.find_or_create_student("octocat").await.unwrap();
+    let assignment = sink.find_or_create_assignment("task01").await.unwrap();
+    sink.write_cell(student, assignment, json!(1)).await.unwrap();
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests[0].method, "PUT");
+    assert_eq!(requests[0].path, "/api/v1/courses/42/assignments/7/submissions/1001");
+    assert_eq!(requests[0].headers["authorization"], "Bearer teacher-token");
+    assert_eq!(requests[0].headers["content-type"], "application/x-www-form-urlencoded");
+    assert_eq!(requests[0].body, "submission%5Bposted_grade%5D=1");
+}
+
+#[tokio::test]
+async fn reads_the_grade_of_the_submission() {
+    let (base_url, server) = common::serve(vec![(200, r#"{"id": 1, "grade": "0.5"}"#)]);
+    let sink = sink(base_url);
+
+    assert_eq!(sink.read_cell(0, 0).await.unwrap().as_deref(), Some("0.5"));
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests[0].method, "GET");
+    assert_eq!(requests[0].path, "/api/v1/courses/42/assignments/7/submissions/1001");
+}
+
+#[tokio::test]
+async fn maps_the_canvas_errors() {
+    let (base_url, server) = common::serve(vec![
+        (401, r#"{"errors": [{"message": "Invalid access token."}]}"#),
+        (404, r#"{"errors": [{"message": "The specified resource does not exist."}]}"#),
+    ]);
+    let sink = sink(base_url);
+
+    let err = sink.write_cell(0, 0, json!(1)).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Auth);
+    assert_eq!(err.status(), Some(401));
+    let err = sink.write_cell(0, 0, json!(1)).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert!(err.to_string().contains("The specified resource does not exist."), "{}", err);
+    server.join().unwrap();
+
+    // Students and assignments missing from the maps are reported before any request
+    let err = sink.find_or_create_student("stranger").await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use github_classroom_spreadsheets_integration::{
+    ApiClient, ClassroomConfig, ClassroomGrade, ClassroomGradeSink, ErrorKind, GradeSink,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+fn client(base_url: String) -> ApiClient {
+    let mut client = ApiClient::default();
+    client.endpoints.classroom_base_url = base_url;
+    client
+}
+
+fn config(grade: ClassroomGrade) -> ClassroomConfig {
+    ClassroomConfig {
+        course_id: "c1".to_string(),
+        grade,
+        roster: HashMap::from([("octocat".to_string(), "octocat@example.edu".to_string())]),
+        assignments: HashMap::from([("task01".to_string(), "cw7".to_string())]),
+    }
+}
+
+const SUBMISSION: &str = r#"{"studentSubmissions": [{"id": "s1", "userId": "1001", "draftGrade": 0.5}]}"#;
+
+#[tokio::test]
+async fn patches_the_grade_of_the_submission() {
+    let (base_url, server) = common::serve(vec![(200, SUBMISSION), (200, r#"{"id": "s1", "assignedGrade": 1}"#)]);
+    let client = client(base_url);
+    let token = "teacher-token".to_string();
+    let sink = ClassroomGradeSink::new(&client, &token, config(ClassroomGrade::Assigned));
+
+    let student = sink.find_or_create_student("octocat").await.unwrap();
+    let assignment = sink.find_or_create_assignment("task01").await.unwrap();
+    sink.write_cell(student, assignment, json!(1)).await.unwrap();
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests[0].method, "GET");
+    assert_eq!(requests[0].path, "/courses/c1/courseWork/cw7/studentSubmissions?userId=octocat%40example.edu");
+    assert_eq!(requests[1].method, "PATCH");
+    assert_eq!(
+        requests[1].path,
+        "/courses/c1/courseWork/cw7/studentSubmissions/s1?updateMask=draftGrade%2CassignedGrade"
+    );
+    assert_eq!(requests[1].headers["authorization"], "Bearer teacher-token");
+    let body: Value = serde_json::from_str(&requests[1].body).unwrap();
+    assert_eq!(body, json!({"draftGrade": 1.0, "assignedGrade": 1.0}));
+}
+
+#[tokio::test]
+async fn reads_the_draft_grade() {
+    let (base_url, server) = common::serve(vec![(200, SUBMISSION)]);
+    let client = client(base_url);
+    let token = "teacher-token".to_string();
+    let sink = ClassroomGradeSink::new(&client, &token, config(ClassroomGrade::Draft));
+
+    assert_eq!(sink.read_cell(0, 0).await.unwrap().as_deref(), Some("0.5"));
+    server.join().unwrap();
+}
+
+#[tokio::test]
+async fn a_missing_submission_is_not_found() {
+    let (base_url, server) = common::serve(vec![(200, "{}")]);
+    let client = client(base_url);
+    let token = "teacher-token".to_string();
+    let sink = ClassroomGradeSink::new(&client, &token, config(ClassroomGrade::Draft));
+
+    let err = sink.write_cell(0, 0, json!(1)).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert!(err.to_string().contains("has no submission of task01"), "{}", err);
+    // Nothing is patched
+    assert_eq!(server.join().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn maps_the_classroom_errors() {
+    let (base_url, server) = common::serve(vec![
+        (200, SUBMISSION),
+        (
+            403,
+            r#"{"error": {"code": 403, "message": "The Developer Console project is not permitted to make this request.",
+                "status": "PERMISSION_DENIED", "details": [{"reason": "ProjectPermissionDenied"}]}}"#,
+        ),
+        (404, r#"{"error": {"code": 404, "message": "Requested entity was not found.", "status": "NOT_FOUND"}}"#),
+    ]);
+    let client = client(base_url);
+    let token = "teacher-token".to_string();
+    let sink = ClassroomGradeSink::new(&client, &token, config(ClassroomGrade::Draft));
+
+    let err = sink.write_cell(0, 0, json!(1)).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Permission);
+    assert_eq!(err.reason(), Some("ProjectPermissionDenied"));
+    assert!(err.to_string().contains("same Google Cloud project"), "{}", err);
+
+    let err = sink.read_cell(0, 0).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert_eq!(err.status(), Some(404));
+    server.join().unwrap();
+}
@@ -0,0 +1,49 @@
+use github_classroom_spreadsheets_integration::{CsvGradeSink, GradeSink, StatusValues, TestResults};
+use serde_json::json;
+use std::fs;
+
+fn results(tests: &[(&str, &str)]) -> TestResults {
+    let tests: Vec<_> = tests
+        .iter()
+        .map(|(name, status)| {
+            json!({"name": name, "status": status, "score": null, "points": null, "test_code": null,
+                   "filename": null, "line_no": null, "duration": null})
+        })
+        .collect();
+    serde_json::from_value(json!({"version": 1, "status": "fail", "max_score": null, "tests": tests})).unwrap()
+}
+
+#[tokio::test]
+async fn creates_the_student_row_and_the_assignment_column() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("grades.csv");
+    let sink = CsvGradeSink::new(&path);
+
+    let student = sink.find_or_create_student("octocat").await.unwrap();
+    let assignment = sink.find_or_create_assignment("task01").await.unwrap();
+    assert_eq!((student, assignment), (1, 1));
+    sink.write_cell(student, assignment, json!(1)).await.unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "github_id,task01\noctocat,1\n");
+    assert_eq!(sink.read_cell(student, assignment).await.unwrap().as_deref(), Some("1"));
+    // Found again rather than added twice
+    assert_eq!(sink.find_or_create_student("octocat").await.unwrap(), 1);
+    assert_eq!(sink.find_or_create_assignment("task01").await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn records_the_results_next_to_the_existing_rows() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("grades.csv");
+    fs::write(&path, "github_id,task01\nalice,1\n").unwrap();
+    let sink = CsvGradeSink::new(&path);
+
+    sink.record_results("bob", &results(&[("task01", "fail"), ("task02", "pass")]), &StatusValues::new(false))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "github_id,task01,task02\nalice,1,\nbob,0,1\n"
+    );
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use github_classroom_spreadsheets_integration::{ApiClient, ErrorKind, ExcelOnlineGradeSink, GradeSink};
+use serde_json::{json, Value};
+
+const WORKBOOK: &str = "/drives/d1/items/i1";
+
+fn client(base_url: String) -> ApiClient {
+    let mut client = ApiClient::default();
+    client.endpoints.graph_base_url = base_url;
+    client
+}
+
+const USED_RANGE: &str = r#"{"address": "'Section A'!A1:B2", "values": [["github_id", "task01"], ["alice", 1]]}"#;
+
+#[tokio::test]
+async fn writes_the_changed_cells_in_one_batch() {
+    let (base_url, server) = common::serve(vec![
+        (200, USED_RANGE),
+        (200, r#"{"responses": [{"id": "1", "status": 200, "body": {}}, {"id": "2", "status": 200, "body": {}}]}"#),
+    ]);
+    let client = client(base_url);
+    let token = "graph-token".to_string();
+    let sink = ExcelOnlineGradeSink::new(&client, &token, WORKBOOK, "Section A");
+
+    sink.write_cells(&[(1, 1, json!(0)), (2, 0, json!("bob"))]).await.unwrap();
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests[0].method, "GET");
+    assert_eq!(
+        requests[0].path,
+        "/drives/d1/items/i1/workbook/worksheets/Section%20A/usedRange(valuesOnly=true)"
+    );
+    assert_eq!(requests[0].headers["authorization"], "Bearer graph-token");
+
+    assert_eq!(requests[1].method, "POST");
+    assert_eq!(requests[1].path, "/$batch");
+    let batch: Value = serde_json::from_str(&requests[1].body).unwrap();
+    assert_eq!(
+        batch,
+        json!({"requests": [
+            {
+                "id": "1",
+                "method": "PATCH",
+                "url": "/drives/d1/items/i1/workbook/worksheets/Section%20A/range(address='B2')",
+                "headers": {"Content-Type": "application/json"},
+                "body": {"values": [[0]]}
+            },
+            {
+                "id": "2",
+                "method": "PATCH",
+                "url": "/drives/d1/items/i1/workbook/worksheets/Section%20A/range(address='A3')",
+                "headers": {"Content-Type": "application/json"},
+                "body": {"values": [["bob"]]},
+                "dependsOn": ["1"]
+            }
+        ]})
+    );
+}
+
+#[tokio::test]
+async fn reports_the_failed_request_of_the_batch() {
+    let (base_url, server) = common::serve(vec![
+        (200, USED_RANGE),
+        (
+            200,
+            r#"{"responses": [
+                {"id": "2", "status": 424, "body": {"error": {"code": "FailedDependency", "message": "Dependency failed"}}},
+                {"id": "1", "status": 404, "body": {"error": {"code": "ItemNotFound", "message": "The worksheet was deleted"}}}
+            ]}"#,
+        ),
+    ]);
+    let client = client(base_url);
+    let token = "graph-token".to_string();
+    let sink = ExcelOnlineGradeSink::new(&client, &token, WORKBOOK, "Section A");
+
+    let err = sink.write_cells(&[(1, 1, json!(0)), (2, 0, json!("bob"))]).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert_eq!(err.reason(), Some("ItemNotFound"));
+    assert!(err.to_string().contains("B2"), "{}", err);
+    server.join().unwrap();
+}
+
+#[tokio::test]
+async fn maps_the_graph_errors() {
+    let (base_url, server) = common::serve(vec![(
+        403,
+        r#"{"error": {"code": "accessDenied", "message": "Access denied"}}"#,
+    )]);
+    let client = client(base_url);
+    let token = "graph-token".to_string();
+    let sink = ExcelOnlineGradeSink::new(&client, &token, WORKBOOK, "Section A");
+
+    let err = sink.read_cell(1, 1).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Permission);
+    assert_eq!(err.status(), Some(403));
+    server.join().unwrap();
+}
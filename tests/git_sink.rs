@@ -0,0 +1,100 @@
+use github_classroom_spreadsheets_integration::{
+    GitConfig, GitGradeSink, GradeSink, StatusValues, TestResults,
+};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .expect("git is installed");
+    assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn results(test: &str) -> TestResults {
+    serde_json::from_value(serde_json::json!({
+        "version": 1,
+        "status": "pass",
+        "max_score": null,
+        "tests": [{"name": test, "status": "pass", "score": null, "points": null, "test_code": null,
+                   "filename": null, "line_no": null, "duration": null}]
+    }))
+    .unwrap()
+}
+
+// Clone of the bare remote whose pushes wait until the other clone is about to push too,
+// so both commit on the same base and one of the pushes is rejected
+fn clone_waiting_for(dir: &Path, remote: &Path, name: &str, other: &str) -> GitConfig {
+    let clone = dir.join(name);
+    git(dir, &["clone", "--quiet", remote.to_str().unwrap(), clone.to_str().unwrap()]);
+    let hook = clone.join(".git/hooks/pre-push");
+    fs::write(
+        &hook,
+        format!(
+            "#!/bin/sh\ntouch {dir}/{name}.ready\nfor i in $(seq 200); do [ -f {dir}/{other}.ready ] && exit 0; sleep 0.05; done\nexit 1\n",
+            dir = dir.display(),
+            name = name,
+            other = other
+        ),
+    )
+    .unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    GitConfig {
+        repo_path: clone,
+        file: "hw1/grades.csv".to_string(),
+        remote: "origin".to_string(),
+        branch: "main".to_string(),
+        push_retries: 3,
+        author_name: "grader".to_string(),
+        author_email: "grader@example.com".to_string(),
+    }
+}
+
+#[test]
+fn concurrent_pushes_are_replayed_on_top_of_each_other() {
+    let dir = tempfile::tempdir().unwrap();
+    let remote = dir.path().join("grades.git");
+    git(dir.path(), &["init", "--quiet", "--bare", remote.to_str().unwrap()]);
+
+    // Both clones start before the branch exists on the remote
+    let first = clone_waiting_for(dir.path(), &remote, "first", "second");
+    let second = clone_waiting_for(dir.path(), &remote, "second", "first");
+
+    let record = |config: GitConfig, student: &'static str| {
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let sink = GitGradeSink::new(config);
+            runtime.block_on(sink.record_results(student, &results("task01"), &StatusValues::new(false)))
+        })
+    };
+    let alice = record(first, "alice");
+    let bob = record(second, "bob");
+    alice.join().unwrap().unwrap();
+    bob.join().unwrap().unwrap();
+
+    let log = git(&remote, &["log", "--format=%s", "main"]);
+    let subjects: Vec<&str> = log.lines().collect();
+    assert_eq!(subjects.len(), 2, "{}", log);
+    assert!(subjects.contains(&"Record the results of alice"));
+    assert!(subjects.contains(&"Record the results of bob"));
+
+    let gradebook = git(&remote, &["show", "main:hw1/grades.csv"]);
+    assert!(gradebook.lines().any(|line| line == "alice,1"), "{}", gradebook);
+    assert!(gradebook.lines().any(|line| line == "bob,1"), "{}", gradebook);
+
+    // Neither the lock nor the temporary file is left in the clones
+    for clone in ["first", "second"] {
+        let status = git(&dir.path().join(clone), &["status", "--porcelain", "--untracked-files=all"]);
+        assert_eq!(status, "", "{}", clone);
+    }
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use github_classroom_spreadsheets_integration::{
+    ApiClient, ErrorKind, GradeSink, MoodleConfig, MoodleGradeFunction, MoodleGradeSink,
+};
+use serde_json::json;
+use std::collections::HashMap;
+
+fn sink(base_url: String, function: MoodleGradeFunction) -> MoodleGradeSink {
+    MoodleGradeSink::new(
+        ApiClient::default(),
+        MoodleConfig {
+            base_url,
+            token: "moodle-token".to_string(),
+            function,
+            course_id: Some("42".to_string()),
+            component: "mod_assign".to_string(),
+            roster: HashMap::from([("octocat".to_string(), "1001".to_string())]),
+            assignments: HashMap::from([("task01".to_string(), "7".to_string())]),
+        },
+    )
+}
+
+#[tokio::test]
+async fn saves_the_grade_of_the_assignment() {
+    // mod_assign_save_grade returns null
+    let (base_url, server) = common::serve(vec![(200, "null")]);
+    let sink = sink(base_url, MoodleGradeFunction::AssignSaveGrade);
+
+    let student = sink.find_or_create_student("octocat").await.unwrap();
+    let assignment = sink.find_or_create_assignment("task01").await.unwrap();
+    sink.write_cell(student, assignment, json!(1)).await.unwrap();
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(requests[0].path, "/webservice/rest/server.php");
+    assert_eq!(
+        requests[0].body,
+        "wstoken=moodle-token&wsfunction=mod_assign_save_grade&moodlewsrestformat=json&assignmentid=7&userid=1001\
+         &grade=1&attemptnumber=-1&addattempt=0&workflowstate=&applytoall=0"
+    );
+}
+
+#[tokio::test]
+async fn updates_the_grade_item_of_the_activity() {
+    let (base_url, server) = common::serve(vec![(200, "0"), (200, "1")]);
+    let sink = sink(base_url, MoodleGradeFunction::UpdateGrades);
+
+    sink.write_cell(0, 0, json!(0.5)).await.unwrap();
+    // Any status but GRADE_UPDATE_OK is a failure
+    let err = sink.write_cell(0, 0, json!(0.5)).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Api);
+    assert!(err.to_string().contains("status 1"), "{}", err);
+
+    let requests = server.join().unwrap();
+    assert!(requests[0].body.contains("wsfunction=core_grades_update_grades&"), "{}", requests[0].body);
+    assert!(requests[0].body.contains("&courseid=42&component=mod_assign&activityid=7&"), "{}", requests[0].body);
+    assert!(requests[0].body.ends_with("&grades%5B0%5D%5Bstudentid%5D=1001&grades%5B0%5D%5Bgrade%5D=0.5"));
+}
+
+#[tokio::test]
+async fn reads_the_grade_of_the_user() {
+    let grades = r#"{"assignments": [{"assignmentid": 7, "grades": [
+        {"userid": 1002, "grade": "2.00000"}, {"userid": 1001, "grade": "1.00000"}]}], "warnings": []}"#;
+    let (base_url, server) = common::serve(vec![(200, grades)]);
+    let sink = sink(base_url, MoodleGradeFunction::AssignSaveGrade);
+
+    assert_eq!(sink.read_cell(0, 0).await.unwrap().as_deref(), Some("1.00000"));
+    assert!(server.join().unwrap()[0].body.contains("wsfunction=mod_assign_get_grades&"));
+}
+
+#[tokio::test]
+async fn an_exception_in_a_successful_response_is_an_error() {
+    let (base_url, server) = common::serve(vec![
+        (200, r#"{"exception": "moodle_exception", "errorcode": "invalidtoken", "message": "Invalid token - token not found"}"#),
+        (200, r#"{"exception": "required_capability_exception", "errorcode": "nopermissions", "message": "Sorry, but you do not currently have permissions to do that"}"#),
+        (200, r#"{"exception": "dml_missing_record_exception", "errorcode": "invalidrecord", "message": "Can't find data record in database table assign."}"#),
+    ]);
+    let sink = sink(base_url, MoodleGradeFunction::AssignSaveGrade);
+
+    let err = sink.write_cell(0, 0, json!(1)).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Auth);
+    assert!(err.to_string().contains("Invalid token - token not found (invalidtoken)"), "{}", err);
+    assert_eq!(sink.write_cell(0, 0, json!(1)).await.unwrap_err().kind(), ErrorKind::Permission);
+    assert_eq!(sink.read_cell(0, 0).await.unwrap_err().kind(), ErrorKind::NotFound);
+    server.join().unwrap();
+}
@@ -0,0 +1,112 @@
+//! Runs against the server of `DATABASE_URL` (e.g. `postgres://postgres@localhost/postgres`),
+//! each test in a schema of its own. Skipped when the variable is not set.
+#![cfg(feature = "postgres")]
+
+use github_classroom_spreadsheets_integration::{
+    ErrorKind, GradeSink, PostgresGradeStore, StatusValues, TestResults,
+};
+use serde_json::json;
+use tokio_postgres::{Client, NoTls};
+
+fn results(tests: &[(&str, &str)]) -> TestResults {
+    let tests: Vec<_> = tests
+        .iter()
+        .map(|(name, status)| {
+            json!({"name": name, "status": status, "score": null, "points": 1, "test_code": null,
+                   "filename": null, "line_no": null, "duration": 12})
+        })
+        .collect();
+    serde_json::from_value(json!({"version": 1, "status": "fail", "max_score": 2, "tests": tests})).unwrap()
+}
+
+// A connection for checking the tables and the URL of the store, both using a fresh `schema`
+async fn scratch_schema(schema: &str) -> Option<(Client, String)> {
+    let url = match std::env::var("DATABASE_URL") {
+        Ok(url) if !url.is_empty() => url,
+        _ => {
+            eprintln!("DATABASE_URL is not set, skipping");
+            return None;
+        }
+    };
+    let (client, connection) = tokio_postgres::connect(&url, NoTls).await.unwrap();
+    tokio::spawn(connection);
+    client
+        .batch_execute(&format!("DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0}; SET search_path = {0}", schema))
+        .await
+        .unwrap();
+
+    let separator = if url.contains('?') { '&' } else { '?' };
+    let store_url = format!("{}{}options=-c%20search_path%3D{}", url, separator, schema);
+    Some((client, store_url))
+}
+
+async fn count(client: &Client, table: &str) -> i64 {
+    client
+        .query_one(&format!("SELECT COUNT(*) FROM {}", table), &[])
+        .await
+        .unwrap()
+        .get(0)
+}
+
+#[tokio::test]
+async fn migrates_once_and_rejects_a_newer_schema() {
+    let Some((client, url)) = scratch_schema("grades_test_migrate").await else {
+        return;
+    };
+
+    PostgresGradeStore::connect(&url, 1).await.unwrap();
+    let applied = count(&client, "schema_migrations").await;
+    assert!(applied > 0);
+
+    // Connecting again finds the schema up to date
+    PostgresGradeStore::connect(&url, 1).await.unwrap();
+    assert_eq!(count(&client, "schema_migrations").await, applied);
+
+    client
+        .execute("INSERT INTO schema_migrations (version) VALUES ($1)", &[&(applied as i32 + 1)])
+        .await
+        .unwrap();
+    let err = PostgresGradeStore::connect(&url, 1).await.err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::Config);
+
+    client.batch_execute("DROP SCHEMA grades_test_migrate CASCADE").await.unwrap();
+}
+
+#[tokio::test]
+async fn records_a_submission_in_one_transaction() {
+    let Some((client, url)) = scratch_schema("grades_test_record").await else {
+        return;
+    };
+    let store = PostgresGradeStore::connect(&url, 2).await.unwrap();
+
+    store
+        .record_results("alice", &results(&[("task01", "pass"), ("task02", "fail")]), &StatusValues::new(false))
+        .await
+        .unwrap();
+    assert_eq!(count(&client, "submissions").await, 1);
+    assert_eq!(count(&client, "test_results").await, 2);
+
+    let student = store.find_or_create_student("alice").await.unwrap();
+    let task01 = store.find_or_create_assignment("task01").await.unwrap();
+    let task02 = store.find_or_create_assignment("task02").await.unwrap();
+    assert_eq!(store.read_cell(student, task01).await.unwrap().as_deref(), Some("1"));
+    assert_eq!(store.read_cell(student, task02).await.unwrap().as_deref(), Some("0"));
+    assert_eq!(count(&client, "students").await, 1);
+
+    // A test result the database rejects rolls back the whole submission
+    client
+        .batch_execute("ALTER TABLE test_results ADD CONSTRAINT no_errors CHECK (status <> 'error')")
+        .await
+        .unwrap();
+    let err = store
+        .record_results("bob", &results(&[("task03", "pass"), ("task04", "error")]), &StatusValues::new(false))
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Api);
+    assert_eq!(count(&client, "submissions").await, 1);
+    assert_eq!(count(&client, "students").await, 1);
+    assert_eq!(count(&client, "assignments").await, 2);
+    assert_eq!(count(&client, "grades").await, 2);
+
+    client.batch_execute("DROP SCHEMA grades_test_record CASCADE").await.unwrap();
+}
@@ -0,0 +1,26 @@
+use github_classroom_spreadsheets_integration::config::inputs::{optional_input, required_input, set_profile};
+use github_classroom_spreadsheets_integration::config::routing::route_from_inputs;
+use std::env;
+
+const ROUTES: &str = r#"{
+    "profiles": {"algo": {"targets": [{"type": "csv", "path": "algo.csv"}]}},
+    "routes": [{"org": "algo-2026", "spreadsheet_id": "algo-sheet", "profile": "algo"}]
+}"#;
+
+// The profile is set once per process, so the whole flow is one test
+#[test]
+fn the_route_overrides_the_inputs_of_a_shared_workflow() {
+    env::set_var("INPUT_ROUTES", ROUTES);
+    env::set_var("GITHUB_REPOSITORY", "algo-2026/hw1-octocat");
+    env::set_var("INPUT_TABLE_ID", "shared-sheet");
+    env::set_var("INPUT_TARGETS", r#"[{"type": "google-sheets"}]"#);
+    env::set_var("INPUT_SHEET_NAME", "Grades");
+
+    let (routing, route) = route_from_inputs().unwrap().unwrap();
+    set_profile(routing.inputs(&route)).unwrap();
+
+    assert_eq!(required_input("INPUT_TABLE_ID").unwrap(), "algo-sheet");
+    assert_eq!(required_input("INPUT_TARGETS").unwrap(), r#"[{"path":"algo.csv","type":"csv"}]"#);
+    // Inputs the route leaves out keep the value of the workflow
+    assert_eq!(optional_input("INPUT_SHEET_NAME").as_deref(), Some("Grades"));
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use async_trait::async_trait;
+use github_classroom_spreadsheets_integration::{
+    AccessToken, ApiClient, CachedTokenProvider, ErrorKind, ExcelOnlineGradeSink, GradeSink, Result, TokenSource,
+};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Hands out `token-1`, `token-2`, ...
+#[derive(Default)]
+struct FakeSource(AtomicU64);
+
+#[async_trait]
+impl TokenSource for FakeSource {
+    async fn fetch_token(&self) -> Result<AccessToken> {
+        let count = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+        AccessToken::new(format!("token-{}", count), 3600)
+    }
+
+    fn cache_key(&self) -> String {
+        "fake".to_string()
+    }
+}
+
+fn client(base_url: String) -> ApiClient {
+    let mut client = ApiClient::default();
+    client.endpoints.graph_base_url = base_url;
+    client
+}
+
+const UNAUTHORIZED: &str = r#"{"error": {"code": "InvalidAuthenticationToken", "message": "Access token has been revoked"}}"#;
+const USED_RANGE: &str = r#"{"address": "Sheet1!A1:B2", "values": [["github_id", "task01"], ["alice", 1]]}"#;
+
+#[tokio::test]
+async fn a_revoked_token_is_replaced_once() {
+    let (base_url, server) = common::serve(vec![
+        (401, UNAUTHORIZED),
+        (200, USED_RANGE),
+        (401, UNAUTHORIZED),
+        (401, UNAUTHORIZED),
+    ]);
+    let client = client(base_url);
+    let dir = tempfile::tempdir().unwrap();
+    let tokens = CachedTokenProvider::new(Box::new(FakeSource::default())).with_cache_dir(dir.path().to_path_buf());
+    let sink = ExcelOnlineGradeSink::new(&client, &tokens, "/drives/d1/items/i1", "Sheet1");
+
+    assert_eq!(sink.read_cell(1, 1).await.unwrap().as_deref(), Some("1"));
+    // A token rejected again right after renewal is an authentication error
+    let err = sink.read_cell(1, 1).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Auth);
+
+    let requests = server.join().unwrap();
+    let used: Vec<&str> = requests.iter().map(|request| request.headers["authorization"].as_str()).collect();
+    assert_eq!(used, ["Bearer token-1", "Bearer token-2", "Bearer token-2", "Bearer token-3"]);
+}
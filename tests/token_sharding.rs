@@ -0,0 +1,65 @@
+mod common;
+
+use async_trait::async_trait;
+use github_classroom_spreadsheets_integration::{
+    AccessToken, ApiClient, CachedTokenProvider, ErrorKind, ExcelOnlineGradeSink, GradeSink, Result,
+    ShardedTokenProvider, ShardingStrategy, TokenSource,
+};
+
+// Always hands out `<name>-token`
+struct FakeSource(&'static str);
+
+#[async_trait]
+impl TokenSource for FakeSource {
+    async fn fetch_token(&self) -> Result<AccessToken> {
+        AccessToken::new(format!("{}-token", self.0), 3600)
+    }
+
+    fn cache_key(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+fn tokens(accounts: &[&'static str]) -> ShardedTokenProvider {
+    accounts.iter().fold(ShardedTokenProvider::new(ShardingStrategy::OnQuota), |tokens, account| {
+        tokens.with_account(account, CachedTokenProvider::new(Box::new(FakeSource(account))))
+    })
+}
+
+fn client(base_url: String) -> ApiClient {
+    let mut client = ApiClient::default();
+    client.endpoints.graph_base_url = base_url;
+    client
+}
+
+const THROTTLED: &str = r#"{"error": {"code": "TooManyRequests", "message": "Rate limit exceeded"}}"#;
+const USED_RANGE: &str = r#"{"address": "Sheet1!A1:B2", "values": [["github_id", "task01"], ["alice", 1]]}"#;
+
+#[tokio::test]
+async fn a_throttled_request_is_retried_with_the_next_account() {
+    let (base_url, server) = common::serve(vec![(429, THROTTLED), (200, USED_RANGE)]);
+    let client = client(base_url);
+    let tokens = tokens(&["a", "b"]);
+    let sink = ExcelOnlineGradeSink::new(&client, &tokens, "/drives/d1/items/i1", "Sheet1");
+
+    assert_eq!(sink.read_cell(1, 1).await.unwrap().as_deref(), Some("1"));
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests[0].headers["authorization"], "Bearer a-token");
+    assert_eq!(requests[1].headers["authorization"], "Bearer b-token");
+    assert_eq!(requests[0].path, requests[1].path);
+    let throttled: Vec<u64> = tokens.usage().iter().map(|usage| usage.throttled).collect();
+    assert_eq!(throttled, [1, 0]);
+}
+
+#[tokio::test]
+async fn a_single_account_is_not_retried() {
+    let (base_url, server) = common::serve(vec![(429, THROTTLED)]);
+    let client = client(base_url);
+    let tokens = tokens(&["a"]);
+    let sink = ExcelOnlineGradeSink::new(&client, &tokens, "/drives/d1/items/i1", "Sheet1");
+
+    let err = sink.read_cell(1, 1).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Quota);
+    assert_eq!(server.join().unwrap().len(), 1);
+}
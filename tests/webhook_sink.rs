@@ -0,0 +1,69 @@
+mod common;
+
+use github_classroom_spreadsheets_integration::sinks::webhook_sink::{signature, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use github_classroom_spreadsheets_integration::{ApiClient, StatusValues, TestResults, WebhookConfig, WebhookSink};
+use serde_json::{json, Value};
+
+fn results() -> TestResults {
+    serde_json::from_value(json!({
+        "version": 1,
+        "status": "pass",
+        "max_score": null,
+        "tests": [{"name": "task01", "status": "pass", "score": null, "points": null, "test_code": null,
+                   "filename": null, "line_no": null, "duration": null}]
+    }))
+    .unwrap()
+}
+
+fn sink(url: String) -> WebhookSink {
+    WebhookSink::new(
+        ApiClient::default(),
+        WebhookConfig {
+            url,
+            secret: Some("hook-secret".to_string()),
+            retries: 3,
+            assignment: Some("hw1".to_string()),
+        },
+    )
+}
+
+#[tokio::test]
+async fn retries_server_errors_with_the_same_signed_delivery() {
+    let (base_url, server) = common::serve(vec![(500, r#"{"error": "busy"}"#), (200, "{}")]);
+    sink(format!("{}/hooks/classroom", base_url))
+        .record_results("octocat", &results(), &StatusValues::new(false))
+        .await
+        .unwrap();
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests.len(), 2);
+    let (first, second) = (&requests[0], &requests[1]);
+    assert_eq!(first.method, "POST");
+    assert_eq!(first.path, "/hooks/classroom");
+    assert_eq!(first.body, second.body);
+    assert_eq!(first.headers["x-classroom-delivery"], second.headers["x-classroom-delivery"]);
+
+    let event: Value = serde_json::from_str(&first.body).unwrap();
+    assert_eq!(first.headers["x-classroom-delivery"], event["delivery_id"].as_str().unwrap());
+    assert_eq!(event["student"], "octocat");
+    assert_eq!(event["assignment"], "hw1");
+
+    let timestamp: u64 = first.headers[&TIMESTAMP_HEADER.to_lowercase()].parse().unwrap();
+    assert_eq!(timestamp, event["timestamp"].as_u64().unwrap());
+    assert_eq!(
+        first.headers[&SIGNATURE_HEADER.to_lowercase()],
+        signature("hook-secret", timestamp, first.body.as_bytes()).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn does_not_retry_client_errors() {
+    let (base_url, server) = common::serve(vec![(400, r#"{"error": "unknown assignment"}"#)]);
+    let err = sink(base_url)
+        .record_results("octocat", &results(), &StatusValues::new(false))
+        .await
+        .unwrap_err();
+
+    assert_eq!(err.status(), Some(400));
+    assert_eq!(server.join().unwrap().len(), 1);
+}
@@ -0,0 +1,84 @@
+use github_classroom_spreadsheets_integration::{GradeSink, StatusValues, TestResults, XlsxGradeSink};
+use serde_json::json;
+use std::path::Path;
+use umya_spreadsheet::Workbook;
+
+fn results(tests: &[(&str, &str)]) -> TestResults {
+    let tests: Vec<_> = tests
+        .iter()
+        .map(|(name, status)| {
+            json!({"name": name, "status": status, "score": null, "points": null, "test_code": null,
+                   "filename": null, "line_no": null, "duration": null})
+        })
+        .collect();
+    serde_json::from_value(json!({"version": 1, "status": "fail", "max_score": null, "tests": tests})).unwrap()
+}
+
+fn read(path: &Path) -> Workbook {
+    umya_spreadsheet::reader::xlsx::read(path).unwrap()
+}
+
+// Gradebook sheet with a bold note below the grades, next to a sheet of the teacher
+fn create_workbook(path: &Path) {
+    let mut book = umya_spreadsheet::new_file();
+    book.set_sheet_name(0, "Grades").unwrap();
+    let grades = book.sheet_by_name_mut("Grades").unwrap();
+    grades.cell_mut("A1").set_value_string("github_id");
+    grades.cell_mut("D5").set_value_string("Late submissions count half");
+    grades.style_mut("D5").font_mut().set_bold(true);
+
+    book.new_sheet("Notes").unwrap();
+    let notes = book.sheet_by_name_mut("Notes").unwrap();
+    notes.cell_mut("A1").set_value_string("Deadline");
+    notes.style_mut("A1").font_mut().set_bold(true);
+    notes.cell_mut("B1").set_value_number(42);
+    umya_spreadsheet::writer::xlsx::write(&book, path).unwrap();
+}
+
+#[tokio::test]
+async fn keeps_the_other_sheets_and_the_formatting() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("grades.xlsx");
+    create_workbook(&path);
+
+    let sink = XlsxGradeSink::new(&path, "Grades");
+    sink.record_results("octocat", &results(&[("task01", "pass")]), &StatusValues::new(false))
+        .await
+        .unwrap();
+
+    let book = read(&path);
+    assert_eq!(book.sheet_count(), 2);
+    let notes = book.sheet_by_name("Notes").unwrap();
+    assert_eq!(notes.value("A1"), "Deadline");
+    assert!(notes.style("A1").font().unwrap().bold());
+    assert_eq!(notes.value("B1"), "42");
+
+    let grades = book.sheet_by_name("Grades").unwrap();
+    assert_eq!(grades.value("D5"), "Late submissions count half");
+    assert!(grades.style("D5").font().unwrap().bold());
+    assert_eq!(grades.value("B1"), "task01");
+    assert_eq!(grades.value("A2"), "octocat");
+    assert_eq!(grades.value("B2"), "1");
+}
+
+#[tokio::test]
+async fn only_grades_are_written_as_numbers() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("grades.xlsx");
+    let sink = XlsxGradeSink::new(&path, "Grades");
+
+    let status_values = StatusValues::from_json(r#"{"error": "TRUE"}"#, false).unwrap();
+    sink.record_results("12345", &results(&[("2024", "pass"), ("task02", "error")]), &status_values)
+        .await
+        .unwrap();
+
+    let book = read(&path);
+    let grades = book.sheet_by_name("Grades").unwrap();
+    let data_type = |coordinate: &str| grades.cell(coordinate).unwrap().data_type().to_string();
+    // A numeric login and a numeric assignment name stay text
+    assert_eq!((grades.value("A2"), data_type("A2")), ("12345".to_string(), "s".to_string()));
+    assert_eq!((grades.value("B1"), data_type("B1")), ("2024".to_string(), "s".to_string()));
+    assert_eq!((grades.value("B2"), data_type("B2")), ("1".to_string(), "n".to_string()));
+    // "TRUE" is not turned into a boolean
+    assert_eq!((grades.value("C2"), data_type("C2")), ("TRUE".to_string(), "s".to_string()));
+}